@@ -0,0 +1,644 @@
+//cli.rs
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use clap::error::ErrorKind;
+use clap::{ArgGroup, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use log::LevelFilter;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{apply_plan, copy_files_matching_patterns, load_jobs, migrate_archive, parse_rate, plan_copy, rebase_links, revert_links, run_job, unmigrated_files, verify_archive};
+use crate::{Action, Check, ConflictPolicy, Error, FileReport, Hours, LinkStyle, Mode, Options, Pattern, Plan, Preserve, RunReport, Step, Throttle, VerifyReport};
+use crate::progress::{count_matching, Meter};
+
+const AFTER_HELP: &str = "\
+A pattern is a file end string like '.fastq.gz', a glob like 'glob:**/raw/*.fastq.gz'
+or a regular expression like 're:^sample_\\d+_R[12]\\.fq\\.gz$' matched against the path relative to the folder.
+
+Called without a command ('file_copy_tool <source_folder> <target_folder> <pattern> ...') it runs 'archive'.
+
+Exit codes: 0 all files processed, 1 fatal error, 2 usage error, 3 some files failed";
+
+/// Identifies files by patterns and copies them to the target folder, verified with sha256 hashes.
+/// Files that are in the target folder already are replaced by links to their copy -
+/// if their sha256 hash still matches the one of the copy.
+#[derive(Parser, Debug)]
+#[command(name = "file_copy_tool", version, after_help = AFTER_HELP)]
+struct Cli {
+    /// Only report failures, warnings and errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Log what is done to stderr; -vv also logs every read
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// text, or json for one JSON event per line
+    #[arg(long, global = true, value_name = "FORMAT", default_value = "text")]
+    output: Format,
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// How the results of a command are written to stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Copy new files and replace the ones already copied by links (the classic two pass run)
+    Archive {
+        #[command(flatten)]
+        folders: Folders,
+        /// Delete each source once its copy is verified instead of linking it;
+        /// the removed paths are recorded in REMOVED_SOURCES in the target folder
+        #[arg(long = "move")]
+        move_sources: bool,
+        #[command(flatten)]
+        copy: CopyArgs,
+        #[command(flatten)]
+        selection: Selection,
+    },
+    /// Only copy new files; files already in the target folder are left alone
+    Copy {
+        #[command(flatten)]
+        folders: Folders,
+        #[command(flatten)]
+        copy: CopyArgs,
+        #[command(flatten)]
+        selection: Selection,
+    },
+    /// Only replace files already copied to the target folder by links; nothing new is copied
+    Link {
+        #[command(flatten)]
+        folders: Folders,
+        #[command(flatten)]
+        copy: CopyArgs,
+        #[command(flatten)]
+        selection: Selection,
+    },
+    /// Show which files are still to be copied or linked, without changing anything
+    Status {
+        #[command(flatten)]
+        folders: Folders,
+        #[command(flatten)]
+        selection: Selection,
+    },
+    /// Print the actions an archive run would take as JSON plan
+    Plan {
+        #[command(flatten)]
+        folders: Folders,
+        #[command(flatten)]
+        selection: Selection,
+    },
+    /// Execute exactly the actions of a plan; files that changed since planning are refused
+    Apply {
+        /// The plan written by 'plan'
+        plan: PathBuf,
+        #[command(flatten)]
+        copy: CopyArgs,
+        // the plan decides which files are processed - there is nothing to exclude
+        #[command(flatten)]
+        limits: Limits,
+    },
+    /// Replace the links to the copies by the real data again
+    Revert {
+        /// The folder with the links
+        folder: String,
+        /// Only links matching one of these are reverted
+        #[arg(required = true, value_parser = parse_pattern)]
+        patterns: Vec<Pattern>,
+        /// Metadata the restored files keep: all or any of times,mode,xattrs,acls
+        #[arg(long, value_name = "LIST", default_value = "mode")]
+        preserve: Preserve,
+        /// Also turn hard linked files back into independent copies
+        #[arg(long)]
+        hard_links: bool,
+        /// Only revert symbolic links pointing to a file below this directory and hard links to one
+        #[arg(long, value_name = "DIR")]
+        backup_root: Option<PathBuf>,
+        #[command(flatten)]
+        selection: Selection,
+    },
+    /// Re-hash the target folder and compare it to its SHA256SUMS manifest
+    Verify {
+        target_folder: String,
+        /// Check only files matching one of these (default: all)
+        #[arg(value_parser = parse_pattern)]
+        patterns: Vec<Pattern>,
+        #[command(flatten)]
+        selection: Selection,
+    },
+    /// Repoint the links in a tree from a moved archive to its new location
+    #[command(name = "rebase-links")]
+    RebaseLinks {
+        tree: String,
+        /// Where the archive was
+        #[arg(long, value_name = "OLD_PREFIX")]
+        from: PathBuf,
+        /// Where the archive is now
+        #[arg(long, value_name = "NEW_PREFIX")]
+        to: PathBuf,
+        /// Repoint only links matching one of these (default: all)
+        #[arg(value_parser = parse_pattern)]
+        patterns: Vec<Pattern>,
+        #[command(flatten)]
+        selection: Selection,
+    },
+    /// Run the jobs of a TOML job file, or only the one named
+    Run {
+        jobs: PathBuf,
+        job_name: Option<String>,
+    },
+    /// Copy a whole archive to a new location and repoint the links in the given trees;
+    /// run it again after an interruption
+    #[command(group(ArgGroup::new("links_known").args(["trees", "no_links"])))]
+    Migrate {
+        old_archive: String,
+        new_archive: String,
+        /// Folders with links into the old archive
+        trees: Vec<String>,
+        /// There are no links into the old archive to repoint
+        #[arg(long)]
+        no_links: bool,
+        /// Delete the old archive at the end - only if every file and every link has been migrated
+        #[arg(long, requires = "links_known", conflicts_with_all = ["exclude", "exclude_dir"])]
+        delete_old: bool,
+        /// Metadata the copies keep: all or any of times,mode,xattrs,acls
+        #[arg(long, value_name = "LIST", default_value = "mode")]
+        preserve: Preserve,
+        #[command(flatten)]
+        selection: Selection,
+    },
+}
+
+#[derive(Args, Debug)]
+struct Folders {
+    source_folder: String,
+    target_folder: String,
+    /// Files matching one of these are processed
+    #[arg(required = true, value_parser = parse_pattern)]
+    patterns: Vec<Pattern>,
+}
+
+/// Options for every command that walks a folder.
+#[derive(Args, Debug)]
+struct Selection {
+    /// Skip files matching <PATTERN> even if they match one of the patterns
+    #[arg(long, value_name = "PATTERN", value_parser = parse_pattern)]
+    exclude: Vec<Pattern>,
+    /// Do not descend into directories matching <PATTERN> (e.g. 'glob:tmp*')
+    #[arg(long, value_name = "PATTERN", value_parser = parse_pattern)]
+    exclude_dir: Vec<Pattern>,
+    #[command(flatten)]
+    limits: Limits,
+}
+
+/// Options for every command that reads files: how much work is done at once.
+#[derive(Args, Debug)]
+#[command(group(ArgGroup::new("rate_limits").multiple(true).args(["max_rate", "max_iops"])))]
+struct Limits {
+    /// Copy and hash <N> files in parallel
+    #[arg(long, short = 'j', value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    workers: u64,
+    /// Read at most <RATE> bytes per second in all workers together, e.g. 200M - copies and hashes count, writes do not
+    #[arg(long, value_name = "RATE", value_parser = parse_rate)]
+    max_rate: Option<u64>,
+    /// Read and write at most <N> times per second in all workers together
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    max_iops: Option<u64>,
+    /// Apply --max-rate and --max-iops only between these local times, e.g. 08:00-18:00
+    #[arg(long, value_name = "HH:MM-HH:MM", requires = "rate_limits")]
+    limit_hours: Option<Hours>,
+}
+
+/// Options for every command that copies or links.
+#[derive(Args, Debug)]
+struct CopyArgs {
+    /// If source and target differ: skip, keep-both or overwrite
+    #[arg(long, value_name = "POLICY", default_value = "skip")]
+    on_conflict: ConflictPolicy,
+    /// Metadata the copies keep: all or any of times,mode,xattrs,acls
+    #[arg(long, value_name = "LIST", default_value = "mode")]
+    preserve: Preserve,
+    /// Links from the sources to their copies: absolute, relative, hard (symlink across file systems) or hard-only
+    #[arg(long, value_name = "STYLE", default_value = "absolute")]
+    links: LinkStyle,
+}
+
+fn parse_pattern(pattern: &str) -> Result<Pattern, Error> {
+    Pattern::parse(pattern)
+}
+
+impl Selection {
+    fn options(&self, include: &[Pattern]) -> Options {
+        Options {
+            include: include.to_vec(),
+            exclude: self.exclude.clone(),
+            exclude_dirs: self.exclude_dir.clone(),
+            ..self.limits.options()
+        }
+    }
+}
+
+impl Limits {
+    fn options(&self) -> Options {
+        Options {
+            workers: self.workers as usize,
+            throttle: (self.max_rate.is_some() || self.max_iops.is_some())
+                .then(|| Arc::new(Throttle::new(self.max_rate, self.max_iops, self.limit_hours))),
+            ..Default::default()
+        }
+    }
+}
+
+impl CopyArgs {
+    fn apply(&self, options: &mut Options) {
+        options.conflict = self.on_conflict;
+        options.preserve = self.preserve;
+        options.links = self.links;
+    }
+}
+
+/// The commands `args` (without the program name) may start with.
+const COMMANDS: [&str; 12] = ["archive", "copy", "link", "status", "plan", "apply", "revert", "verify", "rebase-links", "migrate", "run", "help"];
+
+/// Runs the command line `args` (including the program name) and returns the exit code.
+///
+/// Usage errors go to stderr and return 2. A command line that does not start with a command
+/// is run as `archive`, so `file_copy_tool <source_folder> <target_folder> <pattern> ...` keeps working -
+/// also for a source folder named like a command, see `folder_named_like_command`.
+pub fn run<I, T>(args: I) -> i32
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString>,
+{
+    let mut args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+    // the global options may come before the command
+    let mut first = 1;
+    while let Some(arg) = args.get(first).and_then(|arg| arg.to_str()) {
+        match arg {
+            "--output" => first += 2,
+            "-q" | "--quiet" | "--verbose" => first += 1,
+            _ if arg.starts_with("--output=") || (arg.starts_with("-v") && arg[1..].chars().all(|c| c == 'v')) => first += 1,
+            _ => break,
+        }
+    }
+    if let Some(command) = args.get(first).and_then(|arg| arg.to_str()) {
+        if !COMMANDS.contains(&command) && !["-h", "--help", "-V", "--version"].contains(&command) {
+            args.insert(first, "archive".into());
+        } else if Path::new(command).is_dir() {
+            args = match folder_named_like_command(args, first) {
+                Ok(args) => args,
+                Err(err) => {
+                    let _ = err.print();
+                    return 2;
+                }
+            };
+        }
+    }
+    let cli = match Cli::try_parse_from(args) {
+        Ok(cli) => cli,
+        Err(err) => {
+            let _ = err.print();
+            return if err.use_stderr() { 2 } else { 0 };
+        }
+    };
+    init_logging(cli.quiet, cli.verbose);
+    execute(cli.command, Output { format: cli.output, quiet: cli.quiet })
+}
+
+/// `args` starts with a command that is also a folder in the current directory, e.g. `copy backup .bam`
+/// from a script written before there were commands. The old call form is used if the command does not fit
+/// the arguments or would not find the folder it starts with; if both fit it is a usage error.
+fn folder_named_like_command(args: Vec<OsString>, first: usize) -> Result<Vec<OsString>, clap::Error> {
+    let mut archive = args.clone();
+    archive.insert(first, "archive".into());
+    if Cli::try_parse_from(&archive).is_err() {
+        return Ok(args);
+    }
+    let command_folder_exists = args.get(first + 1)
+        .is_some_and(|arg| arg.to_str().is_some_and(|arg| arg.starts_with('-')) || Path::new(arg).exists());
+    if Cli::try_parse_from(&args).is_err() || !command_folder_exists {
+        return Ok(archive);
+    }
+    let folder = args[first].to_string_lossy();
+    Err(Cli::command().error(ErrorKind::ArgumentConflict, format!(
+        "'{}' is a command and a folder here - write 'archive {} ...' to archive the folder or run the command in another directory", folder, folder)))
+}
+
+/// Sends the log messages of this crate to stderr: warnings and errors with `-q`,
+/// also info by default, debug with `-v` and trace with `-vv`.
+fn init_logging(quiet: bool, verbose: u8) {
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::Warn,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+    // a logger can only be installed once per process, the level is set again for every run
+    let _ = env_logger::Builder::new()
+        .filter_module("file_copy_tool", LevelFilter::Trace)
+        .format(|buf, record| {
+            use std::io::Write;
+            writeln!(buf, "{}: {}", record.level().as_str().to_lowercase(), record.args())
+        })
+        .try_init();
+    log::set_max_level(level);
+}
+
+fn execute(command: Command, out: Output) -> i32 {
+    match command {
+        Command::Archive { folders, move_sources, copy, selection } => {
+            let mode = if move_sources { Mode::Move } else { Mode::Link };
+            copy_run(folders, mode, copy, selection, out)
+        },
+        Command::Copy { folders, copy, selection } => copy_run(folders, Mode::CopyOnly, copy, selection, out),
+        Command::Link { folders, copy, selection } => copy_run(folders, Mode::LinkOnly, copy, selection, out),
+        Command::Status { folders, selection } => {
+            match plan_copy(&folders.source_folder, &folders.target_folder, &selection.options(&folders.patterns)) {
+                Ok(plan) => {
+                    out.status(&plan);
+                    0
+                },
+                Err(err) => out.fatal(err),
+            }
+        },
+        Command::Plan { folders, selection } => {
+            match plan_copy(&folders.source_folder, &folders.target_folder, &selection.options(&folders.patterns)).and_then(|plan| plan.to_json()) {
+                Ok(json) => {
+                    println!("{}", json);
+                    0
+                },
+                Err(err) => out.fatal(err),
+            }
+        },
+        Command::Apply { plan, copy, limits } => {
+            let mut options = limits.options();
+            copy.apply(&mut options);
+            let plan = match Plan::from_file(&plan) {
+                Ok(plan) => plan,
+                Err(err) => return out.fatal(err),
+            };
+            let total = (plan.entries.len() as u64, plan.entries.iter().map(|entry| entry.size).sum());
+            let meter = out.attach_meter(&mut options, Some(total));
+            let result = apply_plan(&plan, &options);
+            meter.finish();
+            out.finish(result)
+        },
+        Command::Revert { folder, patterns, preserve, hard_links, backup_root, selection } => {
+            let mut options = selection.options(&patterns);
+            options.preserve = preserve;
+            options.break_hard_links = hard_links;
+            options.backup_root = backup_root;
+            let meter = out.attach_meter(&mut options, None);
+            let result = revert_links(&folder, &options);
+            meter.finish();
+            out.finish(result)
+        },
+        Command::Verify { target_folder, patterns, selection } => {
+            let mut options = selection.options(&patterns);
+            let meter = out.attach_meter(&mut options, None);
+            let result = verify_archive(&target_folder, &options);
+            meter.finish();
+            match result {
+                Ok(report) => {
+                    out.verify_report(&report);
+                    report.exit_code()
+                },
+                Err(err) => out.fatal(err),
+            }
+        },
+        Command::RebaseLinks { tree, from, to, patterns, selection } => {
+            let mut options = selection.options(&patterns);
+            let meter = out.attach_meter(&mut options, None);
+            let result = rebase_links(&tree, &from, &to, &options);
+            meter.finish();
+            out.finish(result)
+        },
+        Command::Run { jobs, job_name } => run_jobs(&jobs, job_name.as_deref(), out),
+        Command::Migrate { old_archive, new_archive, trees, no_links: _, delete_old, preserve, selection } => {
+            let mut options = selection.options(&[]);
+            options.preserve = preserve;
+            let meter = out.attach_meter(&mut options, None);
+            let result = migrate_archive(&old_archive, &new_archive, &trees, &options);
+            meter.finish();
+            // the selection of the run does not matter - every file of the old archive has to be in the new one
+            let unmigrated = match &result {
+                Ok(report) if delete_old && !report.has_failures() => Some(unmigrated_files(&old_archive, report)),
+                _ => None,
+            };
+            let code = out.finish(result);
+            match unmigrated {
+                Some(files) if files.is_empty() => {
+                    if let Err(err) = fs::remove_dir_all(&old_archive) {
+                        return out.fatal(Error::io(Path::new(&old_archive), err));
+                    }
+                    log::info!("deleted the old archive {}", old_archive);
+                },
+                Some(files) => log::warn!("kept the old archive {} - '{}' and {} more have not been migrated", old_archive, files[0].display(), files.len() - 1),
+                None if delete_old => log::warn!("kept the old archive {} - not everything has been migrated", old_archive),
+                None => (),
+            }
+            code
+        },
+    }
+}
+
+fn copy_run(folders: Folders, mode: Mode, copy: CopyArgs, selection: Selection, out: Output) -> i32 {
+    let mut options = selection.options(&folders.patterns);
+    copy.apply(&mut options);
+    options.mode = mode;
+    let total = out.total(&folders.source_folder, &options);
+    let meter = out.attach_meter(&mut options, total);
+    let result = copy_files_matching_patterns(&folders.source_folder, &folders.target_folder, &options);
+    meter.finish();
+    out.finish(result)
+}
+
+/// Runs every job (or the one named `job_name`) and returns the worst exit code.
+fn run_jobs(path: &Path, job_name: Option<&str>, out: Output) -> i32 {
+    let jobs = match load_jobs(path) {
+        Ok(jobs) => jobs,
+        Err(err) => return out.fatal(err),
+    };
+    let jobs: Vec<_> = jobs.into_iter().filter(|job| job_name.is_none_or(|name| job.name == name)).collect();
+    if let (Some(name), true) = (job_name, jobs.is_empty()) {
+        eprintln!("there is no job '{}' in {}", name, path.display());
+        return 2;
+    }
+    let mut code = 0;
+    for mut job in jobs {
+        match out.format {
+            Format::Text => println!("Job '{}': {} -> {}", job.name, job.source, job.target),
+            Format::Json => out.event("job", json!({ "name": job.name, "source": job.source, "target": job.target })),
+        }
+        let total = out.total(&job.source, &job.options);
+        let meter = out.attach_meter(&mut job.options, total);
+        let result = run_job(&job);
+        meter.finish();
+        let job_code = match result {
+            Ok(report) => {
+                out.report(&report, &format!("Finished '{}'", job.name));
+                report.exit_code()
+            },
+            Err(err) => out.fatal(err),
+        };
+        // a fatal error is worse than failed files
+        code = match (code, job_code) {
+            (1, _) | (_, 1) => 1,
+            (code, job_code) => code.max(job_code),
+        };
+    }
+    code
+}
+
+/// Where and how the results of a command are written.
+#[derive(Debug, Clone, Copy)]
+struct Output {
+    format: Format,
+    /// Only failures are reported.
+    quiet: bool,
+}
+
+/// One line of `--output json`.
+#[derive(Serialize)]
+struct Event<'a, T: Serialize> {
+    event: &'a str,
+    #[serde(flatten)]
+    data: T,
+}
+
+impl Output {
+    fn event(&self, event: &str, data: impl Serialize) {
+        match serde_json::to_string(&Event { event, data }) {
+            Ok(line) => println!("{}", line),
+            Err(err) => log::error!("could not write the {} event: {}", event, err),
+        }
+    }
+
+    /// The files and bytes a copy run has ahead of it, for the ETA of the meter.
+    /// Only counted if the meter is shown - on a big tree the walk is a lot of metadata reads.
+    fn total(&self, source_folder: &str, options: &Options) -> Option<(u64, u64)> {
+        (!self.quiet).then(|| count_matching(source_folder, options))
+    }
+
+    /// Shows the progress of the run with `options` on stderr unless `quiet`;
+    /// `total` is the number of files and bytes ahead, if known.
+    fn attach_meter(&self, options: &mut Options, total: Option<(u64, u64)>) -> Arc<Meter> {
+        let meter = Meter::new();
+        if let Some((files, bytes)) = total {
+            meter.set_total(files, bytes);
+        }
+        if !self.quiet {
+            options.progress = Some(meter.progress());
+        }
+        meter
+    }
+
+    fn fatal(&self, err: Error) -> i32 {
+        if self.format == Format::Json {
+            self.event("error", json!({ "message": err.to_string() }));
+        }
+        log::error!("{}", err);
+        1
+    }
+
+    /// Prints the report and returns the exit code for it.
+    fn finish(&self, result: Result<RunReport, Error>) -> i32 {
+        match result {
+            Ok(report) => {
+                self.report(&report, "Finished");
+                report.exit_code()
+            },
+            Err(err) => self.fatal(err),
+        }
+    }
+
+    /// Prints every file of `report` and the summary line starting with `title`.
+    fn report(&self, report: &RunReport, title: &str) {
+        for file in &report.files {
+            self.file(file);
+        }
+        match self.format {
+            Format::Text if !self.quiet => println!("{}: {}", title, report),
+            Format::Text => (),
+            Format::Json => self.event("summary", json!({
+                "copied": report.count(|a| matches!(a, Action::Copied { .. })),
+                "linked": report.count(|a| matches!(a, Action::Linked { .. })),
+                "moved": report.count(|a| matches!(a, Action::Moved { .. })),
+                "rebased": report.count(|a| matches!(a, Action::Rebased { .. })),
+                "restored": report.count(|a| matches!(a, Action::Restored { .. })),
+                "skipped": report.count(|a| matches!(a, Action::Skipped { .. })),
+                "failed": report.failed(),
+                "bytes_copied": report.bytes_copied,
+                "bytes_freed": report.bytes_freed,
+            })),
+        }
+    }
+
+    fn file(&self, file: &FileReport) {
+        let failed = matches!(file.action, Action::Failed { .. });
+        match self.format {
+            Format::Text if failed => eprintln!("{}", file),
+            Format::Text if !self.quiet => println!("{}", file),
+            Format::Json if failed || !self.quiet => self.event("file", file),
+            _ => (),
+        }
+    }
+
+    fn verify_report(&self, report: &VerifyReport) {
+        for file in &report.files {
+            let ok = matches!(file.check, Check::Ok);
+            match self.format {
+                Format::Text if !ok => eprintln!("{}", file),
+                Format::Text if !self.quiet => println!("{}", file),
+                Format::Json if !ok || !self.quiet => self.event("check", file),
+                _ => (),
+            }
+        }
+        match self.format {
+            Format::Text if !self.quiet => println!("Finished: {}", report),
+            Format::Text => (),
+            Format::Json => self.event("summary", json!({
+                "ok": report.count(|check| matches!(check, Check::Ok)),
+                "missing": report.count(|check| matches!(check, Check::Missing)),
+                "modified": report.count(|check| matches!(check, Check::Modified { .. })),
+                "unlisted": report.count(|check| matches!(check, Check::Unlisted)),
+                "failed": report.count(|check| matches!(check, Check::Failed { .. })),
+            })),
+        }
+    }
+
+    fn status(&self, plan: &Plan) {
+        if self.format == Format::Json {
+            for entry in &plan.entries {
+                self.event("status", entry);
+            }
+            return;
+        }
+        let (mut copy, mut link, mut skip) = ((0, 0), (0, 0), 0);
+        for entry in &plan.entries {
+            match entry.step {
+                Step::Copy => {
+                    println!("to copy  '{}'", entry.source.display());
+                    copy = (copy.0 + 1, copy.1 + entry.size);
+                },
+                Step::Link => {
+                    println!("to link  '{}'", entry.source.display());
+                    link = (link.0 + 1, link.1 + entry.size);
+                },
+                Step::Skip => {
+                    println!("done     '{}': {}", entry.source.display(), entry.reason.as_deref().unwrap_or_default());
+                    skip += 1;
+                },
+            }
+        }
+        println!("Status: {} to copy ({} bytes), {} to link ({} bytes to free), {} done or skipped", copy.0, copy.1, link.0, link.1, skip);
+    }
+}
@@ -0,0 +1,113 @@
+//fast_copy.rs
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use serde::Serialize;
+
+use crate::{Error, Throttle};
+
+/// How the data of a file got into its copy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CopyMethod {
+    /// The copy shares the blocks of the source (FICLONE on btrfs, XFS with reflink=1, ...).
+    Reflink,
+    /// The kernel copied the data without passing it through this process.
+    CopyFileRange,
+    /// Read and written through the buffer of the worker.
+    Buffered,
+}
+
+impl fmt::Display for CopyMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CopyMethod::Reflink => write!(f, "reflink"),
+            CopyMethod::CopyFileRange => write!(f, "copy_file_range"),
+            CopyMethod::Buffered => write!(f, "buffered"),
+        }
+    }
+}
+
+/// Tries to copy `source_path` to the new file `target_path` without reading the data in user space:
+/// a reflink first, then `copy_file_range`.
+/// Returns `None` if neither is supported here - no target file is left behind in that case.
+/// The copy is not verified; that is up to the caller.
+/// A reflink moves no data; `copy_file_range` keeps to `throttle`.
+pub(crate) fn copy(source_path: &Path, target_path: &Path, throttle: Option<&Throttle>) -> Result<Option<CopyMethod>, Error> {
+    #[cfg(target_os = "linux")]
+    {
+        linux::copy(source_path, target_path, throttle)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (source_path, target_path, throttle);
+        Ok(None)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::*;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    pub(super) fn copy(source_path: &Path, target_path: &Path, throttle: Option<&Throttle>) -> Result<Option<CopyMethod>, Error> {
+        let source = fs::File::open(source_path).map_err(|err| Error::io(source_path, err))?;
+        let target = fs::OpenOptions::new().write(true).create_new(true).open(target_path)
+            .map_err(|err| Error::io(target_path, err))?;
+
+        let copied = (|| -> Result<Option<CopyMethod>, Error> {
+            let method = if reflink(&source, &target) {
+                CopyMethod::Reflink
+            } else if copy_file_range(&source, &target, target_path, throttle)? {
+                CopyMethod::CopyFileRange
+            } else {
+                log::debug!("neither a reflink nor copy_file_range works for '{}'", target_path.display());
+                return Ok(None);
+            };
+            target.sync_all().map_err(|err| Error::io(target_path, err))?;
+            Ok(Some(method))
+        })();
+        if !matches!(copied, Ok(Some(_))) {
+            // do not leave a half written copy behind
+            let _ = fs::remove_file(target_path);
+        }
+        copied
+    }
+
+    fn reflink(source: &fs::File, target: &fs::File) -> bool {
+        // SAFETY: both descriptors are open for the duration of the call
+        unsafe { libc::ioctl(target.as_raw_fd(), libc::FICLONE, source.as_raw_fd()) == 0 }
+    }
+
+    /// Returns `false` if the kernel or file system can not do it before anything has been copied.
+    fn copy_file_range(source: &fs::File, target: &fs::File, target_path: &Path, throttle: Option<&Throttle>) -> Result<bool, Error> {
+        // small steps if the copy has to keep to a rate
+        let chunk = if throttle.is_some() { 1 << 20 } else { 1 << 30 };
+        let mut copied = 0;
+        loop {
+            // SAFETY: both descriptors are open; null offsets use and move the file positions
+            let n = unsafe {
+                libc::copy_file_range(source.as_raw_fd(), std::ptr::null_mut(), target.as_raw_fd(), std::ptr::null_mut(), chunk, 0)
+            };
+            if n == 0 {
+                return Ok(true);
+            }
+            if n > 0 {
+                copied += n;
+                // the kernel read `n` bytes and wrote them; the hash of the source reads them once more
+                if let Some(throttle) = throttle {
+                    throttle.take(n as u64, 2);
+                }
+                continue;
+            }
+            let err = io::Error::last_os_error();
+            return match err.raw_os_error() {
+                Some(libc::EXDEV | libc::ENOSYS | libc::EOPNOTSUPP | libc::EINVAL | libc::EPERM) if copied == 0 => Ok(false),
+                Some(libc::EINTR) => continue,
+                _ => Err(Error::io(target_path, err)),
+            };
+        }
+    }
+}
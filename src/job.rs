@@ -0,0 +1,116 @@
+//job.rs
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use serde::Deserialize;
+
+use crate::{copy_files_matching_patterns, parse_rate, Error, Options, Pattern, RunReport, Throttle};
+
+/// One source/target/pattern set of a job file.
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub name: String,
+    pub source: String,
+    pub target: String,
+    pub options: Options,
+}
+
+/// A job as written in the TOML file; every value is given in its command line form.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JobEntry {
+    name: String,
+    source: String,
+    target: String,
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+    #[serde(default)]
+    exclude_dirs: Vec<String>,
+    /// link, move, copy-only or link-only
+    mode: Option<String>,
+    links: Option<String>,
+    on_conflict: Option<String>,
+    preserve: Option<String>,
+    workers: Option<usize>,
+    /// e.g. "200M"
+    max_rate: Option<String>,
+    max_iops: Option<u64>,
+    /// e.g. "08:00-18:00"
+    limit_hours: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JobFile {
+    #[serde(default)]
+    job: Vec<JobEntry>,
+}
+
+/// Reads the jobs of a TOML file, in the order they are written:
+///
+/// ```toml
+/// [[job]]
+/// name = "project-a"
+/// source = "/work/project-a"
+/// target = "/archive/project-a"
+/// include = [".fastq.gz", "glob:**/*.bam"]
+/// exclude_dirs = ["glob:tmp*"]
+/// mode = "copy-only"   # link (default), move, copy-only or link-only
+/// links = "relative"   # absolute (default), relative, hard or hard-only
+/// ```
+///
+/// `on_conflict`, `preserve`, `workers`, `max_rate`, `max_iops` and `limit_hours` take the values of the command line options.
+pub fn load_jobs(path: &Path) -> Result<Vec<Job>, Error> {
+    let invalid = |reason: String| Error::InvalidJobFile { path: path.to_path_buf(), reason };
+    let text = fs::read_to_string(path).map_err(|err| Error::io(path, err))?;
+    let file: JobFile = toml::from_str(&text).map_err(|err| invalid(err.to_string()))?;
+
+    let mut jobs: Vec<Job> = Vec::new();
+    for entry in file.job {
+        if jobs.iter().any(|job| job.name == entry.name) {
+            return Err(invalid(format!("the job name '{}' is used twice", entry.name)));
+        }
+        let job = entry.into_job().map_err(|err| invalid(err.to_string()))?;
+        jobs.push(job);
+    }
+    Ok(jobs)
+}
+
+impl JobEntry {
+    fn into_job(self) -> Result<Job, Error> {
+        let mut options = Options::new(Pattern::parse_all(&self.include)?);
+        options.exclude = Pattern::parse_all(&self.exclude)?;
+        options.exclude_dirs = Pattern::parse_all(&self.exclude_dirs)?;
+        if let Some(mode) = &self.mode {
+            options.mode = mode.parse()?;
+        }
+        if let Some(links) = &self.links {
+            options.links = links.parse()?;
+        }
+        if let Some(policy) = &self.on_conflict {
+            options.conflict = policy.parse()?;
+        }
+        if let Some(preserve) = &self.preserve {
+            options.preserve = preserve.parse()?;
+        }
+        options.workers = self.workers.unwrap_or(1);
+        let max_rate = self.max_rate.as_deref().map(parse_rate).transpose()?;
+        let hours = self.limit_hours.as_deref().map(str::parse).transpose()?;
+        if max_rate.is_some() || self.max_iops.is_some() {
+            options.throttle = Some(Arc::new(Throttle::new(max_rate, self.max_iops, hours)));
+        } else if hours.is_some() {
+            return Err(Error::InvalidOption { option: "limit_hours".to_string(), reason: format!("job '{}' has no max_rate or max_iops to limit", self.name) });
+        }
+        if options.include.is_empty() {
+            return Err(Error::InvalidOption { option: "include".to_string(), reason: format!("job '{}' has no patterns", self.name) });
+        }
+        Ok(Job { name: self.name, source: self.source, target: self.target, options })
+    }
+}
+
+/// Runs `job` like `copy_files_matching_patterns` with its source, target and options.
+pub fn run_job(job: &Job) -> Result<RunReport, Error> {
+    copy_files_matching_patterns(&job.source, &job.target, &job.options)
+}
@@ -1,194 +1,607 @@
 //lib.rs
 
+use std::collections::HashSet;
+use std::fmt;
 use std::fs;
-use std::path::Path;
-use std::io::{self, Read};
-use walkdir::WalkDir;
+use std::path::{Path, PathBuf};
+use std::io::{self, Read, Write};
+use walkdir::{DirEntry, WalkDir};
 use sha2::Digest;
 use sha2::Sha256;
 use hex::encode as hex_encode; // Add this line
 
+pub mod cli;
+mod report;
+pub use report::{Action, FileReport, RunReport};
+mod pattern;
+pub use pattern::Pattern;
+mod fast_copy;
+pub use fast_copy::CopyMethod;
+mod manifest;
+pub use manifest::{Manifest, MANIFEST_NAME, REMOVED_NAME};
+mod metadata;
+mod options;
+pub use options::{ConflictPolicy, LinkStyle, Mode, Options, Preserve};
+mod parallel;
+mod plan;
+pub use plan::{apply_plan, plan_copy, Plan, PlanEntry, Step};
+mod rebase;
+pub use rebase::rebase_links;
+mod migrate;
+pub use migrate::{migrate_archive, unmigrated_files};
+mod job;
+pub use job::{load_jobs, run_job, Job};
+mod progress;
+pub use progress::{Phase, Progress, ProgressEvent};
+mod throttle;
+pub use throttle::{parse_rate, Hours, Throttle};
+mod verify;
+pub use verify::{verify_archive, Check, FileCheck, VerifyReport};
+
 #[cfg(windows)]
 use std::os::windows::fs::{symlink_file};
 #[cfg(unix)]
 use std::os::unix::fs::{symlink};
 
 
+/// Everything that can go wrong in this library.
+/// All variants carry the paths involved so a caller can report or retry them.
+#[derive(Debug)]
+pub enum Error {
+    /// The target file existed already - the source was not copied.
+    TargetExists { source: PathBuf, target: PathBuf },
+    /// The sha256 hash of the copy differs from the original; the copy has been removed again.
+    HashMismatch { source: PathBuf, target: PathBuf, source_hash: String, target_hash: String },
+    /// The symbolic link `link` pointing to `target` could not be created.
+    SymlinkFailed { link: PathBuf, target: PathBuf, err: io::Error },
+    /// The hard link `link` to `target` could not be created.
+    HardLinkFailed { link: PathBuf, target: PathBuf, err: io::Error },
+    /// Renaming `from` to `to` failed.
+    RenameFailed { from: PathBuf, to: PathBuf, err: io::Error },
+    /// The plan file `path` could not be parsed or written.
+    InvalidPlan { path: PathBuf, reason: String },
+    /// The job file `path` could not be read or parsed.
+    InvalidJobFile { path: PathBuf, reason: String },
+    /// A file selection pattern could not be parsed.
+    InvalidPattern { pattern: String, reason: String },
+    /// Line `line` of the manifest `path` is not in the format of sha256sum.
+    InvalidManifest { path: PathBuf, line: usize },
+    /// A command line option is unknown or lacks its value.
+    InvalidOption { option: String, reason: String },
+    /// Any other IO error on `path`.
+    Io { path: PathBuf, err: io::Error },
+}
+
+impl Error {
+    pub fn io(path: &Path, err: io::Error) -> Self {
+        Error::Io { path: path.to_path_buf(), err }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::TargetExists { source, target } =>
+                write!(f, "target {} exists already - {} not copied", target.display(), source.display()),
+            Error::HashMismatch { source, target, source_hash, target_hash } =>
+                write!(f, "sha256 hashes don't match: {} ({}) vs {} ({}) - copy removed",
+                    source.display(), source_hash, target.display(), target_hash),
+            Error::SymlinkFailed { link, target, err } =>
+                write!(f, "could not create symbolic link {} -> {}: {}", link.display(), target.display(), err),
+            Error::HardLinkFailed { link, target, err } =>
+                write!(f, "could not create hard link {} to {}: {}", link.display(), target.display(), err),
+            Error::RenameFailed { from, to, err } =>
+                write!(f, "could not rename {} to {}: {}", from.display(), to.display(), err),
+            Error::InvalidPlan { path, reason } =>
+                write!(f, "invalid plan {}: {}", path.display(), reason),
+            Error::InvalidJobFile { path, reason } =>
+                write!(f, "invalid job file {}: {}", path.display(), reason),
+            Error::InvalidPattern { pattern, reason } =>
+                write!(f, "invalid pattern '{}': {}", pattern, reason),
+            Error::InvalidManifest { path, line } =>
+                write!(f, "{} line {} is no sha256sum line", path.display(), line),
+            Error::InvalidOption { option, reason } =>
+                write!(f, "option {}: {}", option, reason),
+            Error::Io { path, err } =>
+                write!(f, "{}: {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::SymlinkFailed { err, .. }
+            | Error::HardLinkFailed { err, .. }
+            | Error::RenameFailed { err, .. }
+            | Error::Io { err, .. } => Some(err),
+            _ => None,
+        }
+    }
+}
+
+
 #[cfg(windows)]
-fn create_symlink(src: &Path, dst: &Path) -> std::io::Result<()> {
+pub(crate) fn create_symlink(src: &Path, dst: &Path) -> std::io::Result<()> {
     symlink_file(src, dst)
 }
 
 #[cfg(unix)]
-fn create_symlink(src: &Path, dst: &Path) -> std::io::Result<()> {
+pub(crate) fn create_symlink(src: &Path, dst: &Path) -> std::io::Result<()> {
     //return Err(io::Error::new(io::ErrorKind::Other, "See what happens if the link fails."));
     symlink(src, dst)
 }
 
-pub fn copy_files_matching_patterns(source_folder: &str, target_folder: &str, patterns: &[String]) {
+/// Walks `folder` and yields all files and links selected by `options`.
+/// The entries come sorted by name, so every run sees the files in the same order.
+/// Excluded directories are not entered; entries that could not be read are passed on as errors.
+pub(crate) fn walk_matching<'a>(folder: &'a str, options: &'a Options) -> impl Iterator<Item = walkdir::Result<DirEntry>> + 'a {
+    let relative = move |entry: &DirEntry| entry.path().strip_prefix(folder).unwrap_or(entry.path()).to_path_buf();
+    WalkDir::new(folder).sort_by_file_name().into_iter()
+        .filter_entry(move |entry| entry.depth() == 0 || !entry.file_type().is_dir() || !options.prunes_dir(&relative(entry)))
+        .filter(move |entry| match entry {
+            Ok(entry) => !entry.file_type().is_dir() && options.selects_file(&relative(entry)),
+            Err(_) => true,
+        })
+}
+
+pub(crate) fn walk_error(err: walkdir::Error) -> (PathBuf, Action) {
+    let path = err.path().map(Path::to_path_buf).unwrap_or_default();
+    (path, Action::Failed { reason: err.to_string() })
+}
 
-    // the copy returns this error if the file alredy existed:
-    let mut buffer = [0; 1024 * 1024]; // 1 MB buffer
+pub fn copy_files_matching_patterns(source_folder: &str, target_folder: &str, options: &Options) -> Result<RunReport, Error> {
 
-    for entry in WalkDir::new(source_folder).into_iter().filter_map(|e| e.ok()) {
-        let file_path = entry.path();
-        if file_path.is_file() {
-            let file_name = file_path.file_name().unwrap().to_string_lossy();
-            if let Ok(metadata) = fs::metadata(&file_path) {
-                if metadata.file_type().is_symlink() {
-                    //println!("Skipping symbolic link '{:?}'", file_path);
-                    continue;
-                }
-            }
+    if let Err(err) = fs::metadata(source_folder) {
+        return Err(Error::io(Path::new(source_folder), err));
+    }
+    let mut report = RunReport::new();
+    let record = manifest::Recorder::new(Path::new(target_folder));
 
-            for pattern in patterns {
-                if file_name.ends_with(pattern) {
-                    let target_path = Path::new(target_folder).join(file_path.strip_prefix(source_folder).unwrap());
-                    
-                    match copy_file_with_hash_check(&file_path, &target_path, &mut buffer) {
-                        Ok(_) => (),
-                        Err(ref err) if err.to_string() == "link target existed" => {
-                            //println!("repacing {:?} with a link to {:?}", &file_path, &target_path);
-                            replace_with_symlink( &file_path, &target_path );
-                        },
-                        Err(err) => eprintln!("{}", err),
-                    }
-                    break; // Stop processing of one file after copying
-                }
-            }
-        }
+    let actions = parallel::run(walk_matching(source_folder, options), options.workers, |entry, buffer| {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => return walk_error(err),
+        };
+        let file_path = entry.path();
+        let target_path = Path::new(target_folder).join(file_path.strip_prefix(source_folder).unwrap());
+        (file_path.to_path_buf(), PlanEntry::new(file_path, &target_path).execute(buffer, options, &record))
+    });
+    for (path, action) in actions {
+        report.push(path, action);
     }
+    Ok(report)
 }
 
-pub fn revert_links(target_folder: &str, patterns: &[String]) {
-    let mut buffer = [0; 1024 * 1024]; // 1 MB buffer
+pub fn revert_links(target_folder: &str, options: &Options) -> Result<RunReport, Error> {
+
+    if let Err(err) = fs::metadata(target_folder) {
+        return Err(Error::io(Path::new(target_folder), err));
+    }
+    let mut report = RunReport::new();
 
-    for entry in WalkDir::new(target_folder).into_iter().filter_map(|e| e.ok()) {
+    // only process symlinks - and hard links if asked for
+    let backup_files = match (&options.backup_root, options.break_hard_links) {
+        (Some(root), true) => Some(hard_linked_files(root)),
+        _ => None,
+    };
+    let links = walk_matching(target_folder, options).filter(move |entry| match entry {
+        Ok(entry) if entry.path_is_symlink() => match &options.backup_root {
+            Some(root) => points_into(entry.path(), root),
+            None => true,
+        },
+        Ok(entry) => options.break_hard_links && entry.metadata().map(|m| {
+            link_count(&m) > 1 && backup_files.as_ref().is_none_or(|files| file_id(&m).is_some_and(|id| files.contains(&id)))
+        }).unwrap_or(false),
+        Err(_) => true,
+    });
+    let actions = parallel::run(links, options.workers, |entry, buffer| {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => return walk_error(err),
+        };
         let file_path = entry.path();
-        if file_path.is_file() {
-            let file_name = file_path.file_name().unwrap().to_string_lossy();
-            if let Ok(metadata) = fs::symlink_metadata(&file_path) {
-                // only process symlinks
-                if !metadata.file_type().is_symlink() {
-                    //println!("This file is no symlink: {}", file_path.display() );
-                    continue;
-                }
-            }
+        let action = revert_entry(&entry, buffer, options);
+        let bytes = fs::metadata(file_path).map(|m| m.len()).unwrap_or(0);
+        options.report_progress(ProgressEvent::FileDone { path: file_path, bytes });
+        (file_path.to_path_buf(), action)
+    });
+    for (path, action) in actions {
+        report.push(path, action);
+    }
+    Ok(report)
+}
 
-            for pattern in patterns {
-                if file_name.ends_with(pattern) {
-                    if let Err(err) = revert_symlink(&file_path, &mut buffer) {
-                        eprintln!("{}", err);
-                    }
-                }
-            }
-        }
+/// Reverts one symbolic link or breaks up one hard link found by `revert_links`.
+fn revert_entry(entry: &DirEntry, buffer: &mut [u8], options: &Options) -> Action {
+    let file_path = entry.path();
+    if !entry.path_is_symlink() {
+        return match break_hard_link(file_path, buffer, options) {
+            Ok(true) => Action::Restored { target: file_path.to_path_buf(), bytes: entry.metadata().map(|m| m.len()).unwrap_or(0) },
+            Ok(false) => Action::Skipped { reason: "is not a hard link".to_string() },
+            Err(err) => Action::Failed { reason: err.to_string() },
+        };
+    }
+    if !file_path.is_file() {
+        return Action::Failed { reason: "the link target does not exist".to_string() };
     }
+    match revert_symlink(file_path, buffer, options) {
+        Ok(Some(target)) => {
+            let bytes = fs::metadata(file_path).map(|m| m.len()).unwrap_or(0);
+            Action::Restored { target, bytes }
+        },
+        Ok(None) => Action::Skipped { reason: "is not a symbolic link".to_string() },
+        Err(err) => Action::Failed { reason: err.to_string() },
+    }
+}
+
+pub fn calculate_sha256(file_path: &Path, buffer: &mut [u8] ) -> Result<String, Error> {
+    hash_file(file_path, buffer, &Options::default())
 }
 
-pub fn calculate_sha256(file_path: &Path, buffer: &mut [u8] ) -> io::Result<String> {
-    let mut file = fs::File::open(file_path)?;
+/// `calculate_sha256` that reports the bytes read to `options.progress` and keeps to `options.throttle`.
+pub(crate) fn hash_file(file_path: &Path, buffer: &mut [u8], options: &Options) -> Result<String, Error> {
+    let mut file = fs::File::open(file_path).map_err(|err| Error::io(file_path, err))?;
     let mut hasher = Sha256::new();
-    
+
     //let mut buffer = [0; 1024 * 1024]; // 1 MB buffer
-    while let Ok(bytes_read) = file.read( buffer) {
+    loop {
+        let bytes_read = file.read( buffer ).map_err(|err| Error::io(file_path, err))?;
         if bytes_read > 0{
-            //println!("calculate_sha256 read {} bytes", bytes_read);
+            log::trace!("calculate_sha256 read {} bytes from '{}'", bytes_read, file_path.display());
+            options.throttle(bytes_read as u64, 1);
             hasher.update(&buffer[..bytes_read]);
+            options.report_progress(ProgressEvent::Bytes { path: file_path, phase: Phase::Hashing, bytes: bytes_read as u64 });
         } else {
             break;
         }
     }
-    
+
     let result = hasher.finalize();
     let hash_bytes = result.as_slice();
     let hash_hex = hex_encode(hash_bytes);
     Ok(hash_hex)
 }
 
-pub fn copy_file_with_hash_check(source_path: &Path, target_path: &Path, buffer: &mut [u8]) -> io::Result<()> {
-    if !target_path.exists() {
-        if let Some(parent_dir) = target_path.parent() {
-            fs::create_dir_all(parent_dir)?;
-        }
-        fs::copy(source_path, target_path)?;
-        //println!("Copied '{:?}' to '{:?}'", source_path, target_path);
-
-        // Calculate and compare hashes
-        //println!("Calculate hash source");
-        let source_md5 = match calculate_sha256(&source_path, buffer){
-            Ok(v) => v,
-            Err(err) => panic!("calculate_sha256 source hit a wall {err}"),
-        };
-        //println!("Calculate hash target");
-        
-        let target_md5 = match calculate_sha256(&target_path, buffer){
-            Ok(v) => v,
-            Err(err) => panic!("calculate_sha256 target hit a wall {err}"),
-        };
+/// Copies the content of `source_path` to `target_path` and returns the sha256 of the source.
+/// The hash is computed on the same stream that is written, so the source is read only once.
+/// The target must not exist.
+pub fn copy_with_sha256(source_path: &Path, target_path: &Path, buffer: &mut [u8]) -> Result<String, Error> {
+    copy_and_hash(source_path, target_path, buffer, &Options::default())
+}
+
+/// `copy_with_sha256` that reports the bytes written to `options.progress` and keeps to `options.throttle`.
+fn copy_and_hash(source_path: &Path, target_path: &Path, buffer: &mut [u8], options: &Options) -> Result<String, Error> {
+    let mut source = fs::File::open(source_path).map_err(|err| Error::io(source_path, err))?;
+    let mut target = fs::OpenOptions::new().write(true).create_new(true).open(target_path)
+        .map_err(|err| Error::io(target_path, err))?;
+    let mut hasher = Sha256::new();
 
-        if source_md5 != target_md5 {
-            fs::remove_file(&target_path)?;
-            return Err(io::Error::new(io::ErrorKind::Other, "MD5 hashes don't match. Files may not be copied correctly."));
+    let mut copy = || -> Result<(), Error> {
+        loop {
+            let bytes_read = source.read( buffer ).map_err(|err| Error::io(source_path, err))?;
+            if bytes_read == 0 {
+                break;
+            }
+            // read once, hashed and written
+            options.throttle(bytes_read as u64, 2);
+            hasher.update(&buffer[..bytes_read]);
+            target.write_all(&buffer[..bytes_read]).map_err(|err| Error::io(target_path, err))?;
+            options.report_progress(ProgressEvent::Bytes { path: source_path, phase: Phase::Copying, bytes: bytes_read as u64 });
         }
-        println!("Copied '{:?}' to '{:?}'", source_path, target_path);
-        Ok(())
-    } else {
-        //println!("The target path exists already - not copying!");
-        return Err(io::Error::new(io::ErrorKind::Other, "link target existed"));
+        target.flush().map_err(|err| Error::io(target_path, err))?;
+        target.sync_all().map_err(|err| Error::io(target_path, err))
+    };
+    if let Err(err) = copy() {
+        // do not leave a half written copy behind
+        let _ = fs::remove_file(target_path);
+        return Err(err);
     }
+
+    Ok(hex_encode(hasher.finalize().as_slice()))
 }
 
-pub fn revert_symlink(file_path: &Path, buffer: &mut [u8;  1024 * 1024]) -> io::Result<()> {
-    println!("Trying to fix file {}", file_path.display());
+/// The temporary name a copy to `target_path` is written to: a hidden file in the same directory.
+pub(crate) fn temp_path(target_path: &Path) -> PathBuf {
+    let name = target_path.file_name().unwrap_or_default().to_string_lossy();
+    target_path.with_file_name(format!(".{}.partial", name))
+}
 
-    if let Ok(target_path) = fs::read_link(&file_path) {
-        match fs::remove_file(&file_path){
-            Ok(_) => (),
-            Err(err) => panic!("remove file hit an error {err}")
-        };
-        if let Err(err) = copy_file_with_hash_check(&target_path, &file_path, buffer){
-        	eprintln!("{}", err);
+/// Copies `source_path` to the temporary name of `target_path` and checks the copy by reading it back.
+/// A reflink or `copy_file_range` is tried before the data is copied through `buffer`;
+/// the source is hashed separately then.
+/// The copy gets the metadata selected by `options.preserve`.
+/// Returns the temporary file, the sha256 of the verified copy and how it was made;
+/// on any error the temporary file is gone again.
+pub(crate) fn copy_to_temp(source_path: &Path, target_path: &Path, buffer: &mut [u8], options: &Options) -> Result<(PathBuf, String, CopyMethod), Error> {
+    let temp_path = temp_path(target_path);
+    let source_metadata = fs::metadata(source_path).map_err(|err| Error::io(source_path, err))?;
+    // left over from a run that has been killed
+    if fs::symlink_metadata(&temp_path).is_ok() {
+        fs::remove_file(&temp_path).map_err(|err| Error::io(&temp_path, err))?;
+    }
+
+    // Calculate and compare hashes
+    let (source_hash, method) = match fast_copy::copy(source_path, &temp_path, options.throttle.as_deref())? {
+        Some(method) => {
+            options.report_progress(ProgressEvent::Bytes { path: source_path, phase: Phase::Copying, bytes: source_metadata.len() });
+            match hash_file(source_path, buffer, options) {
+                Ok(hash) => (hash, method),
+                Err(err) => {
+                    let _ = fs::remove_file(&temp_path);
+                    return Err(err);
+                }
+            }
+        },
+        None => (copy_and_hash(source_path, &temp_path, buffer, options)?, CopyMethod::Buffered),
+    };
+    let target_hash = match hash_file(&temp_path, buffer, options) {
+        Ok(hash) => hash,
+        Err(err) => {
+            let _ = fs::remove_file(&temp_path);
+            return Err(err);
         }
-        println!("Reverted the process for {}: Removed symbolic link and replaced it with a copy of the target file.", file_path.display());
+    };
+
+    if source_hash != target_hash {
+        log::warn!("the copy '{}' of '{}' does not match (sha256 {} vs {})", temp_path.display(), source_path.display(), target_hash, source_hash);
+        fs::remove_file(&temp_path).map_err(|err| Error::io(&temp_path, err))?;
+        return Err(Error::HashMismatch {
+            source: source_path.to_path_buf(), target: target_path.to_path_buf(), source_hash, target_hash
+        });
+    }
+    if let Err(err) = metadata::preserve(source_path, &source_metadata, &temp_path, &options.preserve) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err);
+    }
+    log::debug!("copied '{}' to '{}' ({}), sha256 {}", source_path.display(), temp_path.display(), method, target_hash);
+    Ok((temp_path, target_hash, method))
+}
+
+/// Moves the verified temporary file over `target_path` and makes the new name durable.
+pub(crate) fn rename_into_place(temp_path: &Path, target_path: &Path) -> Result<(), Error> {
+    if let Err(err) = fs::rename(temp_path, target_path) {
+        let _ = fs::remove_file(temp_path);
+        return Err(Error::RenameFailed { from: temp_path.to_path_buf(), to: target_path.to_path_buf(), err });
+    }
+    #[cfg(unix)]
+    if let Some(parent_dir) = target_path.parent() {
+        // the rename is only safe on disk once the directory is
+        let parent_dir = if parent_dir.as_os_str().is_empty() { Path::new(".") } else { parent_dir };
+        fs::File::open(parent_dir).and_then(|dir| dir.sync_all()).map_err(|err| Error::io(parent_dir, err))?;
     }
     Ok(())
 }
 
-pub fn replace_with_symlink( file_2_replace: &Path, link_target: &Path) -> Option<()> {
+/// Copies `source_path` to `target_path` and checks the copy by reading it back.
+/// The copy is written to a temporary name next to the target, synced and verified
+/// and only then renamed - a killed run never leaves a truncated file under the real name.
+/// The copy gets the metadata selected by `options.preserve`.
+/// Returns the sha256 of the verified copy.
+pub fn copy_file_with_hash_check(source_path: &Path, target_path: &Path, buffer: &mut [u8], options: &Options) -> Result<String, Error> {
+    copy_new_file(source_path, target_path, buffer, options).map(|(hash, _)| hash)
+}
+
+/// `copy_file_with_hash_check` that also tells how the copy was made.
+pub(crate) fn copy_new_file(source_path: &Path, target_path: &Path, buffer: &mut [u8], options: &Options) -> Result<(String, CopyMethod), Error> {
+    if target_path.exists() {
+        log::debug!("'{}' exists already - not copying '{}'", target_path.display(), source_path.display());
+        return Err(Error::TargetExists { source: source_path.to_path_buf(), target: target_path.to_path_buf() });
+    }
+    if let Some(parent_dir) = target_path.parent() {
+        fs::create_dir_all(parent_dir).map_err(|err| Error::io(parent_dir, err))?;
+    }
+
+    let (temp_path, hash, method) = copy_to_temp(source_path, target_path, buffer, options)?;
+    if target_path.exists() {
+        // somebody else was faster
+        let _ = fs::remove_file(&temp_path);
+        return Err(Error::TargetExists { source: source_path.to_path_buf(), target: target_path.to_path_buf() });
+    }
+    rename_into_place(&temp_path, target_path)?;
+    Ok((hash, method))
+}
+
+/// The shortest path from the directory `from` to `to`; both have to be absolute and canonicalized.
+pub(crate) fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<_> = from.components().collect();
+    let to: Vec<_> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut path: PathBuf = from[common..].iter().map(|_| Path::new("..")).collect();
+    path.extend(&to[common..]);
+    path
+}
+
+/// The path the symbolic link `link` points to; a relative link is resolved from the directory the link is in.
+pub(crate) fn link_target(link: &Path) -> io::Result<PathBuf> {
+    let target_path = fs::read_link(link)?;
+    Ok(match link.parent() {
+        Some(dir) if target_path.is_relative() => dir.join(target_path),
+        _ => target_path,
+    })
+}
+
+/// Does the symbolic link `link` point to a file below `root`?
+/// Both sides are canonicalized if possible, so `..` in relative links and symlinked mount points do not matter.
+fn points_into(link: &Path, root: &Path) -> bool {
+    let target_path = match link_target(link) {
+        Ok(target_path) => target_path,
+        Err(_) => return false,
+    };
+    let target_path = fs::canonicalize(&target_path).unwrap_or(target_path);
+    let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    target_path.starts_with(root)
+}
+
+/// The ids of the files below `root` with more than one link, to find their other links in a work area.
+fn hard_linked_files(root: &Path) -> HashSet<(u64, u64)> {
+    WalkDir::new(root).into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| link_count(metadata) > 1)
+        .filter_map(|metadata| file_id(&metadata))
+        .collect()
+}
+
+/// Replaces the symbolic link `file_path` with a copy of the file it points to.
+/// The copy is written next to the link, verified and then renamed over it,
+/// so on any error the link is left as it was.
+/// The copy gets the metadata of the link target selected by `options.preserve`.
+/// Returns the link target or None if `file_path` is no link.
+pub fn revert_symlink(file_path: &Path, buffer: &mut [u8], options: &Options) -> Result<Option<PathBuf>, Error> {
+
+    if let Ok(target_path) = link_target(file_path) {
+        let (temp_path, _, _) = copy_to_temp(&target_path, file_path, buffer, options)?;
+        rename_into_place(&temp_path, file_path)?;
+        return Ok(Some(target_path));
+    }
+    Ok(None)
+}
+
+pub fn replace_with_symlink( file_2_replace: &Path, link_target: &Path, options: &Options) -> Result<(), Error> {
 
     // Create the symlink
-    if fs::read_link(&file_2_replace).is_ok(){
+    if fs::read_link(file_2_replace).is_ok(){
         // the file is already a link - that should be the default if this is run regularly.
-        return Some(())
-    }
-    match fs::canonicalize(&link_target) {
-        Ok(abs_target) => {
-            // Rename the file to be replaced
-            let renamed_file = file_2_replace.with_extension("bak");
-            if let Err(err) = fs::rename(&file_2_replace, &renamed_file) {
-                panic!("Error renaming file: {}", err);
-            }
-            if let Err(err) = create_symlink(&abs_target, &file_2_replace) {
-                eprintln!("Error creating symlink: {}", err);
-                
-                if let Err(err) = fs::rename(&renamed_file ,&file_2_replace ) {
-                    // rename the file back to it's original name 
-                    panic!("Error renaming file: {}", err);
-                }           
-                None
-            }else {
-                // cool the renamed is not necessary any more
-                if let Err(remove_err) = fs::remove_file(&renamed_file) {
-                    eprintln!("Error removing renamed file: {}", remove_err);
-                }
-                println!("Created symbolic link here '{:?}' linking to '{:?}'", file_2_replace, link_target);
-                Some(())
-            } 
+        return Ok(())
+    }
+    let abs_target = fs::canonicalize(link_target).map_err(|err| Error::io(link_target, err))?;
+    let abs_target = match options.links {
+        LinkStyle::Relative => {
+            let dir = match file_2_replace.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            let abs_dir = fs::canonicalize(dir).map_err(|err| Error::io(dir, err))?;
+            relative_path(&abs_dir, &abs_target)
         },
-        Err(err) => {
-            eprintln!("Failed to get the absolute path for {:?} with err {err}", link_target);
-            None
+        _ => abs_target,
+    };
+
+    // Create the link under a name of its own and rename it over the file - the file stays if anything fails
+    let temp_path = link_temp_path(file_2_replace)?;
+    if let Err(err) = create_symlink(&abs_target, &temp_path) {
+        return Err(Error::SymlinkFailed { link: file_2_replace.to_path_buf(), target: abs_target, err });
+    }
+    rename_into_place(&temp_path, file_2_replace)
+}
+
+/// The name a link that replaces `file_path` is created under before it is renamed over the file:
+/// a hidden file in the same directory, so workers replacing files side by side never share it.
+fn link_temp_path(file_path: &Path) -> Result<PathBuf, Error> {
+    let name = file_path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = file_path.with_file_name(format!(".{}.link", name));
+    // left over from a run that has been killed
+    if fs::symlink_metadata(&temp_path).is_ok() {
+        fs::remove_file(&temp_path).map_err(|err| Error::io(&temp_path, err))?;
+    }
+    Ok(temp_path)
+}
+
+/// Replaces `file_2_replace` with a link to `link_target` in the style of `options.links`.
+/// A hard link needs both files on the same file system; otherwise `LinkStyle::Hard` falls back
+/// to an absolute symlink and `LinkStyle::HardOnly` fails.
+pub fn replace_with_link(file_2_replace: &Path, link_target: &Path, options: &Options) -> Result<(), Error> {
+    match options.links {
+        LinkStyle::Absolute | LinkStyle::Relative => replace_with_symlink(file_2_replace, link_target, options),
+        LinkStyle::Hard | LinkStyle::HardOnly => {
+            if same_device(file_2_replace, link_target)? {
+                replace_with_hard_link(file_2_replace, link_target)
+            } else if options.links == LinkStyle::Hard {
+                replace_with_symlink(file_2_replace, link_target, options)
+            } else {
+                Err(Error::HardLinkFailed {
+                    link: file_2_replace.to_path_buf(),
+                    target: link_target.to_path_buf(),
+                    err: io::Error::from(io::ErrorKind::CrossesDevices),
+                })
+            }
         },
     }
 }
+
+pub fn replace_with_hard_link(file_2_replace: &Path, link_target: &Path) -> Result<(), Error> {
+
+    if same_file(file_2_replace, link_target) {
+        // linked by an earlier run
+        return Ok(())
+    }
+    let temp_path = link_temp_path(file_2_replace)?;
+    if let Err(err) = fs::hard_link(link_target, &temp_path) {
+        return Err(Error::HardLinkFailed { link: file_2_replace.to_path_buf(), target: link_target.to_path_buf(), err });
+    }
+    rename_into_place(&temp_path, file_2_replace)
+}
+
+/// Replaces a file that shares its data with other hard links by an independent, verified copy of itself.
+/// Returns `false` if there is nothing to break up.
+pub fn break_hard_link(file_path: &Path, buffer: &mut [u8], options: &Options) -> Result<bool, Error> {
+    let metadata = fs::symlink_metadata(file_path).map_err(|err| Error::io(file_path, err))?;
+    if !metadata.file_type().is_file() || link_count(&metadata) < 2 {
+        return Ok(false);
+    }
+    let (temp_path, _, _) = copy_to_temp(file_path, file_path, buffer, options)?;
+    rename_into_place(&temp_path, file_path)?;
+    Ok(true)
+}
+
+/// Do both paths point to the same data?
+pub(crate) fn same_file(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (fs::metadata(a), fs::metadata(b)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (a, b);
+        false
+    }
+}
+
+/// Are both paths on the same file system? Without device ids (not unix) a hard link is simply tried.
+fn same_device(a: &Path, b: &Path) -> Result<bool, Error> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let a = fs::metadata(a).map_err(|err| Error::io(a, err))?;
+        let b = fs::metadata(b).map_err(|err| Error::io(b, err))?;
+        Ok(a.dev() == b.dev())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (a, b);
+        Ok(true)
+    }
+}
+
+/// Device and inode - the same for all hard links to a file.
+fn file_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some((metadata.dev(), metadata.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+fn link_count(metadata: &fs::Metadata) -> u64 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        metadata.nlink()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        1
+    }
+}
@@ -0,0 +1,212 @@
+//manifest.rs
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use crate::{rename_into_place, temp_path, Error};
+
+/// The name of the manifest in the root of every target folder.
+pub const MANIFEST_NAME: &str = "SHA256SUMS";
+
+/// The record of all sources deleted by `Mode::Move`, next to the manifest.
+/// One `sha256sum` line per removed file, with the path the source had.
+pub const REMOVED_NAME: &str = "REMOVED_SOURCES";
+
+/// The sha256 hashes of all files ever copied into a target folder.
+///
+/// The file is written in the format of `sha256sum`, with paths relative to the target folder,
+/// so `cd <target_folder> && sha256sum -c SHA256SUMS` checks the archive without this tool.
+/// Every run appends to it; the line of a file that is overwritten is replaced.
+/// If a path is listed more than once anyway (e.g. by an older version) the last line counts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    entries: BTreeMap<PathBuf, String>,
+}
+
+impl Manifest {
+    /// The manifest file of the target folder `root`.
+    pub fn path(root: &Path) -> PathBuf {
+        root.join(MANIFEST_NAME)
+    }
+
+    /// Reads the manifest of `root`; a missing manifest is an empty one.
+    pub fn load(root: &Path) -> Result<Self, Error> {
+        let path = Self::path(root);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(Error::io(&path, err)),
+        };
+        let mut manifest = Self::default();
+        for (i, line) in text.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            match parse_line(line) {
+                Some((hash, relative_path)) => manifest.insert(relative_path, hash),
+                None => return Err(Error::InvalidManifest { path, line: i + 1 }),
+            }
+        }
+        Ok(manifest)
+    }
+
+    pub fn insert(&mut self, relative_path: PathBuf, hash: String) {
+        self.entries.insert(relative_path, hash);
+    }
+
+    /// The hash recorded for the file at `relative_path`.
+    pub fn get(&self, relative_path: &Path) -> Option<&str> {
+        self.entries.get(relative_path).map(String::as_str)
+    }
+
+    /// All (relative path, hash) pairs sorted by path.
+    pub fn iter(&self) -> impl Iterator<Item = (&PathBuf, &String)> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Appends `entries` (relative path, hash) to the manifest of `root`.
+    pub fn append(root: &Path, entries: &[(PathBuf, String)]) -> Result<(), Error> {
+        let lines: String = entries.iter()
+            .map(|(relative_path, hash)| format_line(hash, &relative_name(relative_path)))
+            .collect();
+        append_lines(&Self::path(root), &lines)
+    }
+}
+
+impl Manifest {
+    /// Replaces the manifest of `root` with this one: written to a temporary file and renamed over it,
+    /// so a run that is killed leaves the old or the new manifest behind, never a part of it.
+    pub(crate) fn save(&self, root: &Path) -> Result<(), Error> {
+        let path = Self::path(root);
+        let temp_path = temp_path(&path);
+        let lines: String = self.entries.iter()
+            .map(|(relative_path, hash)| format_line(hash, &relative_name(relative_path)))
+            .collect();
+        fs::write(&temp_path, lines)
+            .and_then(|_| fs::File::open(&temp_path)?.sync_all())
+            .map_err(|err| Error::io(&temp_path, err))?;
+        rename_into_place(&temp_path, &path)
+    }
+}
+
+fn append_lines(path: &Path, lines: &str) -> Result<(), Error> {
+    if lines.is_empty() {
+        return Ok(());
+    }
+    // one write, so concurrent runs do not mix their lines
+    fs::OpenOptions::new().create(true).append(true).open(path)
+        .and_then(|mut file| file.write_all(lines.as_bytes()).and_then(|_| file.sync_data()))
+        .map_err(|err| Error::io(path, err))
+}
+
+/// Adds the copies of a run to the manifest of its target folder, each one as soon as it is in place,
+/// and the sources it deletes to the `REMOVED_NAME` record, so a run that is killed leaves nothing unrecorded.
+pub(crate) struct Recorder {
+    root: PathBuf,
+    /// The manifest as it was when the run started.
+    listed: Manifest,
+    /// Keeps the workers from appending while the manifest is rewritten.
+    writing: Mutex<()>,
+}
+
+impl Recorder {
+    pub(crate) fn new(root: &Path) -> Self {
+        let listed = Manifest::load(root).unwrap_or_else(|err| {
+            log::warn!("{} - files linked by this run are listed again", err);
+            Manifest::default()
+        });
+        Recorder { root: root.to_path_buf(), listed, writing: Mutex::new(()) }
+    }
+
+    /// `target` is a verified copy with the content `hash`.
+    /// A file listed with another hash has been overwritten - its line is replaced, as `sha256sum -c` checks every line.
+    pub(crate) fn copied(&self, target: &Path, hash: &str) -> Result<(), Error> {
+        let relative_path = match target.strip_prefix(&self.root) {
+            Ok(relative_path) => relative_path,
+            Err(_) => return Ok(()),
+        };
+        let _writing = self.writing.lock().unwrap_or_else(PoisonError::into_inner);
+        match self.listed.get(relative_path) {
+            Some(listed) if listed != hash => {
+                let mut manifest = Manifest::load(&self.root)?;
+                manifest.insert(relative_path.to_path_buf(), hash.to_string());
+                manifest.save(&self.root)
+            },
+            _ => Manifest::append(&self.root, &[(relative_path.to_path_buf(), hash.to_string())]),
+        }
+    }
+
+    /// `target` has just been hashed to `hash` - it is added unless the manifest lists it like that already
+    /// (e.g. the copy of a run that was killed before it got to record it).
+    pub(crate) fn verified(&self, target: &Path, hash: &str) -> Result<(), Error> {
+        match target.strip_prefix(&self.root) {
+            Ok(relative_path) if self.listed.get(relative_path) == Some(hash) => Ok(()),
+            _ => self.copied(target, hash),
+        }
+    }
+
+    /// `source` (an absolute path) with the content `hash` has just been deleted by `Mode::Move` - it goes into the `REMOVED_NAME` record.
+    pub(crate) fn removed(&self, source: &Path, hash: &str) -> Result<(), Error> {
+        append_lines(&self.root.join(REMOVED_NAME), &format_line(hash, &source.to_string_lossy()))
+    }
+}
+
+/// The relative path with '/' separators on every platform.
+fn relative_name(relative_path: &Path) -> String {
+    relative_path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// sha256sum escapes names containing a backslash or a newline and marks the line with a leading backslash.
+fn format_line(hash: &str, name: &str) -> String {
+    if name.contains('\\') || name.contains('\n') {
+        format!("\\{}  {}\n", hash, name.replace('\\', "\\\\").replace('\n', "\\n"))
+    } else {
+        format!("{}  {}\n", hash, name)
+    }
+}
+
+fn parse_line(line: &str) -> Option<(String, PathBuf)> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+    let (hash, name) = line.split_once(' ')?;
+    // ' ' marks text mode, '*' binary mode - both are the same to us
+    let name = name.strip_prefix(' ').or_else(|| name.strip_prefix('*'))?;
+    if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) || name.is_empty() {
+        return None;
+    }
+    let name = if escaped {
+        let mut unescaped = String::with_capacity(name.len());
+        let mut chars = name.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                unescaped.push(c);
+                continue;
+            }
+            match chars.next()? {
+                'n' => unescaped.push('\n'),
+                '\\' => unescaped.push('\\'),
+                _ => return None,
+            }
+        }
+        unescaped
+    } else {
+        name.to_string()
+    };
+    Some((hash.to_ascii_lowercase(), PathBuf::from(name)))
+}
@@ -0,0 +1,62 @@
+//metadata.rs
+
+use std::fs;
+use std::path::Path;
+use filetime::FileTime;
+
+use crate::{Error, Preserve};
+
+/// The extended attributes that hold the POSIX ACLs of a file.
+#[cfg(unix)]
+const ACL_ATTRIBUTES: [&str; 2] = ["system.posix_acl_access", "system.posix_acl_default"];
+
+/// Gives `target` the metadata of the source selected by `preserve`.
+/// `source_metadata` has to be taken before the source is read - reading it changes the access time.
+pub(crate) fn preserve(source: &Path, source_metadata: &fs::Metadata, target: &Path, preserve: &Preserve) -> Result<(), Error> {
+    // before the mode - a read-only mode would keep an unprivileged user from setting them
+    #[cfg(unix)]
+    if preserve.xattrs || preserve.acls {
+        copy_xattrs(source, target, preserve)?;
+    }
+    #[cfg(not(unix))]
+    let _ = source;
+    if preserve.mode {
+        fs::set_permissions(target, source_metadata.permissions()).map_err(|err| Error::io(target, err))?;
+    }
+    // last - nothing may touch the file afterwards
+    if preserve.times {
+        let atime = FileTime::from_last_access_time(source_metadata);
+        let mtime = FileTime::from_last_modification_time(source_metadata);
+        filetime::set_file_times(target, atime, mtime).map_err(|err| Error::io(target, err))?;
+    }
+    Ok(())
+}
+
+/// Copies the extended attributes and/or ACLs.
+/// Attributes outside the `user` namespace need privileges - they are copied if possible and skipped otherwise.
+#[cfg(unix)]
+fn copy_xattrs(source: &Path, target: &Path, preserve: &Preserve) -> Result<(), Error> {
+    let names = match xattr::list(source) {
+        Ok(names) => names,
+        // the file system does not know extended attributes - there is nothing to copy
+        Err(err) if err.raw_os_error() == Some(libc::ENOTSUP) => return Ok(()),
+        Err(err) => return Err(Error::io(source, err)),
+    };
+    for name in names {
+        let text = name.to_string_lossy();
+        let is_acl = ACL_ATTRIBUTES.contains(&text.as_ref());
+        if (is_acl && !preserve.acls) || (!is_acl && !preserve.xattrs) {
+            continue;
+        }
+        let value = match xattr::get(source, &name).map_err(|err| Error::io(source, err))? {
+            Some(value) => value,
+            None => continue,
+        };
+        if let Err(err) = xattr::set(target, &name, &value) {
+            if is_acl || text.starts_with("user.") {
+                return Err(Error::io(target, err));
+            }
+        }
+    }
+    Ok(())
+}
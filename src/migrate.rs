@@ -0,0 +1,156 @@
+//migrate.rs
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::{copy_new_file, hash_file, parallel, rebase_links, walk_error, walk_matching};
+use crate::{Action, Error, Manifest, Options, Pattern, ProgressEvent, RunReport, MANIFEST_NAME, REMOVED_NAME};
+
+/// Copies the archive `old_folder` to `new_folder` and repoints the links in `trees` to the new copy.
+///
+/// Every file is copied and verified like by `copy_file_with_hash_check` and also has to match
+/// the hash the manifest of the old archive lists for it. Each verified file is added to the manifest
+/// of the new archive right away, so an interrupted migration can simply be started again:
+/// files listed there with the same hash and size are not read again, other files already in the new archive are re-hashed.
+/// The links are repointed with `rebase_links`.
+///
+/// Nothing in `old_folder` is changed. It can be deleted if the report has no failures
+/// and `unmigrated_files` finds nothing.
+pub fn migrate_archive(old_folder: &str, new_folder: &str, trees: &[String], options: &Options) -> Result<RunReport, Error> {
+    if let Err(err) = fs::metadata(old_folder) {
+        return Err(Error::io(Path::new(old_folder), err));
+    }
+    let new_root = Path::new(new_folder);
+    fs::create_dir_all(new_root).map_err(|err| Error::io(new_root, err))?;
+    let old_manifest = Manifest::load(Path::new(old_folder))?;
+    let new_manifest = Manifest::load(new_root)?;
+
+    let mut archive_options = options.clone();
+    if archive_options.include.is_empty() {
+        archive_options.include.push(Pattern::suffix(""));
+    }
+    archive_options.exclude.push(Pattern::regex(&format!("^{}$", regex::escape(MANIFEST_NAME)))?);
+
+    let mut report = RunReport::new();
+    let actions = parallel::run(walk_matching(old_folder, &archive_options), options.workers, |entry, buffer| {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => return walk_error(err),
+        };
+        let relative_path = entry.path().strip_prefix(old_folder).unwrap_or(entry.path()).to_path_buf();
+        let file = ArchiveFile {
+            old_path: entry.path().to_path_buf(),
+            new_path: new_root.join(&relative_path),
+            expected: old_manifest.get(&relative_path).map(str::to_string),
+            migrated: new_manifest.get(&relative_path).map(str::to_string),
+            // a record of removed sources, not part of the archive
+            listed: relative_path != Path::new(REMOVED_NAME),
+            relative_path,
+        };
+        let action = file.migrate(new_root, buffer, options);
+        let bytes = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        options.report_progress(ProgressEvent::FileDone { path: &file.old_path, bytes });
+        (file.old_path, action)
+    });
+    for (path, action) in actions {
+        report.push(path, action);
+    }
+
+    // every link in the trees, not only the ones matching the archive patterns
+    let link_options = Options { include: Vec::new(), exclude: Vec::new(), exclude_dirs: Vec::new(), ..options.clone() };
+    for tree in trees {
+        let rebased = rebase_links(tree, Path::new(old_folder), new_root, &link_options)?;
+        for file in rebased.files {
+            report.push(file.path, file.action);
+        }
+    }
+    Ok(report)
+}
+
+/// The files and links below `old_folder` that `report` does not show as copied to or verified in the new archive -
+/// whatever the patterns of the migration were. The old archive must not be deleted while there are any.
+pub fn unmigrated_files(old_folder: &str, report: &RunReport) -> Vec<PathBuf> {
+    let migrated: HashSet<&Path> = report.files.iter()
+        .filter(|file| matches!(file.action, Action::Copied { .. } | Action::Skipped { .. }))
+        .map(|file| file.path.as_path())
+        .collect();
+    let manifest = Path::new(old_folder).join(MANIFEST_NAME);
+    WalkDir::new(old_folder).sort_by_file_name().into_iter()
+        .filter_map(|entry| match entry {
+            Ok(entry) if entry.file_type().is_dir() || entry.path() == manifest || migrated.contains(entry.path()) => None,
+            Ok(entry) => Some(entry.into_path()),
+            Err(err) => Some(err.path().unwrap_or(Path::new(old_folder)).to_path_buf()),
+        })
+        .collect()
+}
+
+struct ArchiveFile {
+    old_path: PathBuf,
+    new_path: PathBuf,
+    relative_path: PathBuf,
+    /// The hash in the manifest of the old archive.
+    expected: Option<String>,
+    /// The hash in the manifest of the new archive - set if an earlier run copied the file.
+    migrated: Option<String>,
+    /// Whether the file belongs into the manifest.
+    listed: bool,
+}
+
+impl ArchiveFile {
+    fn migrate(&self, new_root: &Path, buffer: &mut [u8], options: &Options) -> Action {
+        let size = match fs::metadata(&self.old_path) {
+            Ok(metadata) => metadata.len(),
+            Err(err) => return Action::Failed { reason: err.to_string() },
+        };
+        if fs::symlink_metadata(&self.new_path).is_ok() {
+            return self.check_migrated(new_root, size, buffer, options);
+        }
+
+        let (hash, method) = match copy_new_file(&self.old_path, &self.new_path, buffer, options) {
+            Ok(copied) => copied,
+            Err(err) => return Action::Failed { reason: err.to_string() },
+        };
+        if let Some(expected) = self.expected.as_ref().filter(|expected| **expected != hash) {
+            let _ = fs::remove_file(&self.new_path);
+            return Action::Failed { reason: format!("does not match the manifest of the old archive (sha256 {} vs {}) - copy removed", hash, expected) };
+        }
+        if let Err(err) = self.record(new_root, &hash) {
+            return Action::Failed { reason: err.to_string() };
+        }
+        Action::Copied { target: self.new_path.clone(), bytes: size, hash, method }
+    }
+
+    /// The file is in the new archive already - from an earlier run or put there some other way.
+    fn check_migrated(&self, new_root: &Path, size: u64, buffer: &mut [u8], options: &Options) -> Action {
+        let same_size = fs::metadata(&self.new_path).map(|metadata| metadata.len() == size).unwrap_or(false);
+        if same_size && self.migrated.is_some() && (self.expected.is_none() || self.migrated == self.expected) {
+            return Action::Skipped { reason: "migrated by an earlier run".to_string() };
+        }
+        let expected = match &self.expected {
+            Some(expected) => expected.clone(),
+            None => match hash_file(&self.old_path, buffer, options) {
+                Ok(hash) => hash,
+                Err(err) => return Action::Failed { reason: err.to_string() },
+            },
+        };
+        match hash_file(&self.new_path, buffer, options) {
+            Ok(found) if found == expected => match self.record(new_root, &found) {
+                Ok(_) => Action::Skipped { reason: "verified in the new archive".to_string() },
+                Err(err) => Action::Failed { reason: err.to_string() },
+            },
+            Ok(found) => Action::Failed {
+                reason: format!("differs from '{}' (sha256 {} vs {})", self.new_path.display(), expected, found)
+            },
+            Err(err) => Action::Failed { reason: err.to_string() },
+        }
+    }
+
+    fn record(&self, new_root: &Path, hash: &str) -> Result<(), Error> {
+        if !self.listed {
+            return Ok(());
+        }
+        Manifest::append(new_root, &[(self.relative_path.clone(), hash.to_string())])
+    }
+}
@@ -0,0 +1,207 @@
+//options.rs
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::{Error, Pattern, Progress, ProgressEvent, Throttle};
+
+/// What to do if the source and the existing target differ in content.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Leave both files alone and report the conflict (the default).
+    #[default]
+    Skip,
+    /// Copy the source next to the target under a versioned name (`name.v1.ext`)
+    /// and link to that copy on the next run.
+    KeepBoth,
+    /// Replace the target with a verified copy of the source; the next run links to it.
+    Overwrite,
+}
+
+impl std::str::FromStr for ConflictPolicy {
+    type Err = Error;
+
+    fn from_str(policy: &str) -> Result<Self, Error> {
+        match policy {
+            "skip" => Ok(ConflictPolicy::Skip),
+            "keep-both" => Ok(ConflictPolicy::KeepBoth),
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            _ => Err(Error::InvalidOption { option: "--on-conflict".to_string(), reason: format!("'{}' is not one of skip, keep-both or overwrite", policy) }),
+        }
+    }
+}
+
+/// What happens to a source file once its copy in the target folder is verified.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// The first run copies, the next one replaces the source by a link (the default).
+    #[default]
+    Link,
+    /// The source is deleted as soon as its copy is verified - no link is left behind.
+    Move,
+    /// New files are copied, files already in the target are left alone.
+    CopyOnly,
+    /// Files already in the target are linked, new files are not copied.
+    LinkOnly,
+}
+
+impl std::str::FromStr for Mode {
+    type Err = Error;
+
+    fn from_str(mode: &str) -> Result<Self, Error> {
+        match mode {
+            "link" => Ok(Mode::Link),
+            "move" => Ok(Mode::Move),
+            "copy-only" => Ok(Mode::CopyOnly),
+            "link-only" => Ok(Mode::LinkOnly),
+            _ => Err(Error::InvalidOption { option: "mode".to_string(), reason: format!("'{}' is not one of link, move, copy-only or link-only", mode) }),
+        }
+    }
+}
+
+/// How a source file points to its archived copy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LinkStyle {
+    /// A symlink to the canonicalized target (the default).
+    #[default]
+    Absolute,
+    /// A symlink with the shortest relative path from the source's directory to the target.
+    /// Survives moving or mounting the project together with its archive somewhere else.
+    Relative,
+    /// A hard link if source and target are on the same file system, an absolute symlink otherwise.
+    Hard,
+    /// Like `Hard`, but a file on another file system fails instead of being symlinked.
+    HardOnly,
+}
+
+impl std::str::FromStr for LinkStyle {
+    type Err = Error;
+
+    fn from_str(style: &str) -> Result<Self, Error> {
+        match style {
+            "absolute" => Ok(LinkStyle::Absolute),
+            "relative" => Ok(LinkStyle::Relative),
+            "hard" => Ok(LinkStyle::Hard),
+            "hard-only" => Ok(LinkStyle::HardOnly),
+            _ => Err(Error::InvalidOption { option: "--links".to_string(), reason: format!("'{}' is not one of absolute, relative, hard or hard-only", style) }),
+        }
+    }
+}
+
+/// Which metadata of the source is given to a copy.
+/// The default keeps the permission bits only - like `fs::copy` did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Preserve {
+    /// Modification and access time.
+    pub times: bool,
+    /// The permission bits.
+    pub mode: bool,
+    /// Extended attributes, e.g. `user.*` provenance.
+    pub xattrs: bool,
+    /// POSIX ACLs.
+    pub acls: bool,
+}
+
+impl Default for Preserve {
+    fn default() -> Self {
+        Preserve { times: false, mode: true, xattrs: false, acls: false }
+    }
+}
+
+impl Preserve {
+    pub fn all() -> Self {
+        Preserve { times: true, mode: true, xattrs: true, acls: true }
+    }
+}
+
+impl std::str::FromStr for Preserve {
+    type Err = Error;
+
+    /// `all` or a comma separated list of `times`, `mode`, `xattrs` and `acls`.
+    fn from_str(list: &str) -> Result<Self, Error> {
+        if list == "all" {
+            return Ok(Self::all());
+        }
+        let mut preserve = Preserve { times: false, mode: false, xattrs: false, acls: false };
+        for item in list.split(',') {
+            match item {
+                "times" => preserve.times = true,
+                "mode" => preserve.mode = true,
+                "xattrs" => preserve.xattrs = true,
+                "acls" => preserve.acls = true,
+                _ => return Err(Error::InvalidOption { option: "--preserve".to_string(), reason: format!("'{}' is not one of all, times, mode, xattrs or acls", item) }),
+            }
+        }
+        Ok(preserve)
+    }
+}
+
+/// The settings shared by `copy_files_matching_patterns`, `plan_copy` and `revert_links`.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Files matching one of these patterns are processed.
+    pub include: Vec<Pattern>,
+    /// Files matching one of these are left alone even if they match an include pattern.
+    pub exclude: Vec<Pattern>,
+    /// Directories matching one of these are not entered at all.
+    pub exclude_dirs: Vec<Pattern>,
+    /// How many files are copied and hashed at the same time (0 and 1 both mean one).
+    pub workers: usize,
+    /// What to do when a source should be linked to a target with different content.
+    pub conflict: ConflictPolicy,
+    /// The metadata every copy gets from its source.
+    pub preserve: Preserve,
+    /// The kind of link that replaces a copied source.
+    pub links: LinkStyle,
+    /// Whether sources are copied, linked or deleted.
+    pub mode: Mode,
+    /// `revert_links` also turns hard linked files back into independent copies.
+    pub break_hard_links: bool,
+    /// `revert_links` only reverts symbolic links pointing to a file below this directory
+    /// and hard links to a file below it.
+    pub backup_root: Option<PathBuf>,
+    /// Called while files are copied and hashed.
+    pub progress: Option<Progress>,
+    /// Limits the reads and writes of all workers together.
+    pub throttle: Option<Arc<Throttle>>,
+}
+
+impl Options {
+    pub fn new(include: Vec<Pattern>) -> Self {
+        Options { include, ..Default::default() }
+    }
+
+    /// Hands `event` to the progress callback, if there is one.
+    pub(crate) fn report_progress(&self, event: ProgressEvent) {
+        if let Some(progress) = &self.progress {
+            progress.report(event);
+        }
+    }
+
+    /// Waits as long as the throttle asks for before `ops` reads or writes of `bytes` bytes.
+    pub(crate) fn throttle(&self, bytes: u64, ops: u64) {
+        if let Some(throttle) = &self.throttle {
+            throttle.take(bytes, ops);
+        }
+    }
+
+    /// Is the file at `relative_path` part of this run?
+    pub fn selects_file(&self, relative_path: &Path) -> bool {
+        self.include.iter().any(|pattern| pattern.matches(relative_path))
+            && !self.exclude.iter().any(|pattern| pattern.matches(relative_path))
+    }
+
+    /// Would a walk reach and select the file at `relative_path`?
+    /// Like `selects_file`, but also checks that none of its directories is excluded.
+    pub fn selects(&self, relative_path: &Path) -> bool {
+        self.selects_file(relative_path)
+            && !relative_path.ancestors().skip(1)
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .any(|dir| self.prunes_dir(dir))
+    }
+
+    /// Is the directory at `relative_path` skipped together with everything below it?
+    pub fn prunes_dir(&self, relative_path: &Path) -> bool {
+        self.exclude_dirs.iter().any(|pattern| pattern.matches(relative_path))
+    }
+}
@@ -0,0 +1,65 @@
+//parallel.rs
+
+use std::sync::mpsc::{channel, sync_channel};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Runs `work` on all `items` using `workers` threads, each with its own 1 MB buffer.
+///
+/// The items are fed through a bounded queue, so a long walk over the source folder
+/// does not pile up in memory. The results come back in the order of `items`
+/// no matter how many workers ran or which one finished first.
+pub(crate) fn run<T, R, I, F>(items: I, workers: usize, work: F) -> Vec<R>
+where
+    I: Iterator<Item = T>,
+    T: Send,
+    R: Send,
+    F: Fn(T, &mut [u8]) -> R + Sync,
+{
+    let workers = workers.max(1);
+    if workers == 1 {
+        let mut buffer = vec![0; 1024 * 1024]; // 1 MB buffer
+        return items.map(|item| work(item, &mut buffer)).collect();
+    }
+
+    let (queue, jobs) = sync_channel::<(usize, T)>(workers * 2);
+    let jobs = Arc::new(Mutex::new(jobs));
+    let (done, results) = channel::<(usize, R)>();
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let jobs = Arc::clone(&jobs);
+            let done = done.clone();
+            let work = &work;
+            scope.spawn(move || {
+                let mut buffer = vec![0; 1024 * 1024]; // 1 MB buffer
+                loop {
+                    // the lock is only held while waiting for the next job
+                    let job = match jobs.lock() {
+                        Ok(jobs) => jobs.recv(),
+                        Err(_) => break,
+                    };
+                    match job {
+                        Ok((index, item)) => {
+                            if done.send((index, work(item, &mut buffer))).is_err() {
+                                break;
+                            }
+                        },
+                        Err(_) => break, // the queue is closed and empty
+                    }
+                }
+            });
+        }
+        drop(done);
+        for job in items.enumerate() {
+            if queue.send(job).is_err() {
+                break;
+            }
+        }
+        drop(queue);
+    });
+
+    let mut results: Vec<(usize, R)> = results.into_iter().collect();
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
@@ -0,0 +1,108 @@
+//pattern.rs
+
+use std::fmt;
+use std::path::{Component, Path};
+use std::str::FromStr;
+use globset::{GlobBuilder, GlobMatcher};
+use regex::Regex;
+
+use crate::Error;
+
+/// Selects files by their path relative to the folder that is processed.
+///
+/// Written on the command line as
+///  - `.fastq.gz` or `suffix:.fastq.gz` - the relative path ends with this string (the default),
+///  - `glob:**/raw/*.fastq.gz` - a shell glob; `*` does not cross a `/`, `**` does.
+///    A glob without a `/` is matched against the file name only,
+///  - `re:^sample_\d+_R[12]\.fq\.gz$` - a regular expression searched in the relative path.
+///
+/// The relative path always uses `/` as separator.
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Suffix(String),
+    Glob { glob: String, matcher: GlobMatcher },
+    Regex(Regex),
+}
+
+impl Pattern {
+    pub fn suffix(suffix: &str) -> Self {
+        Pattern::Suffix(suffix.to_string())
+    }
+
+    pub fn glob(glob: &str) -> Result<Self, Error> {
+        let matcher = GlobBuilder::new(glob)
+            .literal_separator(true)
+            .build()
+            .map_err(|err| Error::InvalidPattern { pattern: glob.to_string(), reason: err.to_string() })?
+            .compile_matcher();
+        Ok(Pattern::Glob { glob: glob.to_string(), matcher })
+    }
+
+    pub fn regex(regex: &str) -> Result<Self, Error> {
+        Regex::new(regex)
+            .map(Pattern::Regex)
+            .map_err(|err| Error::InvalidPattern { pattern: regex.to_string(), reason: err.to_string() })
+    }
+
+    /// Parses the command line form described above.
+    pub fn parse(pattern: &str) -> Result<Self, Error> {
+        if let Some(glob) = pattern.strip_prefix("glob:") {
+            Self::glob(glob)
+        } else if let Some(regex) = pattern.strip_prefix("re:") {
+            Self::regex(regex)
+        } else if let Some(suffix) = pattern.strip_prefix("suffix:") {
+            Ok(Self::suffix(suffix))
+        } else {
+            Ok(Self::suffix(pattern))
+        }
+    }
+
+    pub fn parse_all(patterns: &[String]) -> Result<Vec<Self>, Error> {
+        patterns.iter().map(|pattern| Self::parse(pattern)).collect()
+    }
+
+    /// Checks the path relative to the processed folder.
+    pub fn matches(&self, relative_path: &Path) -> bool {
+        let path = slash_path(relative_path);
+        match self {
+            Pattern::Suffix(suffix) => path.ends_with(suffix.as_str()),
+            Pattern::Glob { glob, matcher } => {
+                if glob.contains('/') {
+                    matcher.is_match(&path)
+                } else {
+                    matcher.is_match(path.rsplit('/').next().unwrap_or_default())
+                }
+            },
+            Pattern::Regex(regex) => regex.is_match(&path),
+        }
+    }
+}
+
+/// The path with `/` separators on every platform.
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+impl FromStr for Pattern {
+    type Err = Error;
+
+    fn from_str(pattern: &str) -> Result<Self, Error> {
+        Self::parse(pattern)
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Suffix(suffix) => write!(f, "{}", suffix),
+            Pattern::Glob { glob, .. } => write!(f, "glob:{}", glob),
+            Pattern::Regex(regex) => write!(f, "re:{}", regex),
+        }
+    }
+}
@@ -0,0 +1,298 @@
+//plan.rs
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use serde::{Deserialize, Serialize};
+
+use crate::{copy_new_file, copy_to_temp, hash_file, manifest::Recorder, parallel, rename_into_place, replace_with_link, same_file, walk_matching};
+use crate::{Action, ConflictPolicy, CopyMethod, Error, Mode, Options, ProgressEvent, RunReport};
+
+/// What `apply_plan` will do with one file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Step {
+    /// Copy the source to the target and verify the copy.
+    Copy,
+    /// The target exists - replace the source with a link to it if both have the same content.
+    Link,
+    /// Leave the source alone.
+    Skip,
+}
+
+/// One file of a `Plan` together with the size and mtime of the source at planning time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanEntry {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub step: Step,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub size: u64,
+    /// Seconds since the unix epoch.
+    pub mtime: u64,
+    pub mtime_nsec: u32,
+}
+
+/// The full list of actions `copy_files_matching_patterns` would take - computed without touching any file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Plan {
+    pub source_folder: PathBuf,
+    pub target_folder: PathBuf,
+    pub entries: Vec<PlanEntry>,
+}
+
+impl PlanEntry {
+    /// Decides what to do with `source`, which is copied to `target`.
+    pub(crate) fn new(source: &Path, target: &Path) -> Self {
+        let mut entry = PlanEntry {
+            source: source.to_path_buf(),
+            target: target.to_path_buf(),
+            step: Step::Skip,
+            reason: None,
+            size: 0,
+            mtime: 0,
+            mtime_nsec: 0,
+        };
+        let metadata = match fs::symlink_metadata(source) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                entry.reason = Some(err.to_string());
+                return entry;
+            }
+        };
+        (entry.size, entry.mtime, entry.mtime_nsec) = fingerprint(&metadata);
+        if metadata.file_type().is_symlink() {
+            entry.reason = Some("is a symbolic link already".to_string());
+        } else if same_file(source, target) {
+            entry.reason = Some("is a hard link to the target already".to_string());
+        } else if target.exists() {
+            entry.step = Step::Link;
+        } else {
+            entry.step = Step::Copy;
+        }
+        entry
+    }
+
+    /// Checks that the source is still the file that has been planned for.
+    /// Returns why it is not.
+    fn changed(&self) -> Option<String> {
+        match fs::symlink_metadata(&self.source) {
+            Ok(metadata) if metadata.file_type().is_symlink() => Some("has been replaced by a symbolic link since planning".to_string()),
+            Ok(metadata) => {
+                let (size, mtime, mtime_nsec) = fingerprint(&metadata);
+                if size != self.size {
+                    Some(format!("size changed since planning ({} -> {} bytes)", self.size, size))
+                } else if (mtime, mtime_nsec) != (self.mtime, self.mtime_nsec) {
+                    Some("modification time changed since planning".to_string())
+                } else {
+                    None
+                }
+            },
+            Err(err) => Some(format!("can not be read any more: {}", err)),
+        }
+    }
+
+    /// Executes this step without any further checks.
+    pub(crate) fn execute(&self, buffer: &mut [u8], options: &Options, record: &Recorder) -> Action {
+        let action = self.execute_step(buffer, options, record);
+        options.report_progress(ProgressEvent::FileDone { path: &self.source, bytes: self.size });
+        action
+    }
+
+    fn execute_step(&self, buffer: &mut [u8], options: &Options, record: &Recorder) -> Action {
+        log::debug!("{:?} '{}' -> '{}'", self.step, self.source.display(), self.target.display());
+        match (self.step, options.mode) {
+            (Step::Copy, Mode::LinkOnly) => return Action::Skipped { reason: "not in the target yet".to_string() },
+            (Step::Link, Mode::CopyOnly) => return Action::Skipped { reason: "in the target already".to_string() },
+            _ => (),
+        }
+        match self.step {
+            Step::Copy => match copy_new_file(&self.source, &self.target, buffer, options) {
+                Ok((hash, method)) => self.copied(&self.target, hash, method, buffer, options, record),
+                Err(Error::TargetExists { .. }) => Action::Failed { reason: "the target has been created since planning".to_string() },
+                Err(err) => Action::Failed { reason: err.to_string() },
+            },
+            Step::Link => self.link(buffer, options, record),
+            Step::Skip => Action::Skipped { reason: self.reason.clone().unwrap_or_default() },
+        }
+    }
+
+    /// Replaces the source with a link to the target (or deletes it in `Mode::Move`)
+    /// - but only if both have the same content. Otherwise `options.conflict` decides.
+    fn link(&self, buffer: &mut [u8], options: &Options, record: &Recorder) -> Action {
+        let source_hash = match hash_file(&self.source, buffer, options) {
+            Ok(hash) => hash,
+            Err(err) => return Action::Failed { reason: err.to_string() },
+        };
+        let target_hash = match hash_file(&self.target, buffer, options) {
+            Ok(hash) => hash,
+            Err(err) => return Action::Failed { reason: err.to_string() },
+        };
+        if source_hash == target_hash {
+            return self.link_to(&self.target, source_hash, options, record);
+        }
+
+        match options.conflict {
+            ConflictPolicy::Skip => Action::Skipped {
+                reason: format!("conflict: differs from '{}' (sha256 {} vs {})", self.target.display(), source_hash, target_hash)
+            },
+            ConflictPolicy::KeepBoth => {
+                let mut version = 1;
+                loop {
+                    let versioned = versioned_path(&self.target, version);
+                    if !versioned.exists() {
+                        return self.copy_to(&versioned, buffer, options, record);
+                    }
+                    match hash_file(&versioned, buffer, options) {
+                        // copied by an earlier run
+                        Ok(hash) if hash == source_hash => return self.link_to(&versioned, source_hash, options, record),
+                        Ok(_) => version += 1,
+                        Err(err) => return Action::Failed { reason: err.to_string() },
+                    }
+                }
+            },
+            ConflictPolicy::Overwrite => {
+                let copied = copy_to_temp(&self.source, &self.target, buffer, options)
+                    .and_then(|(temp_path, hash, method)| rename_into_place(&temp_path, &self.target).map(|_| (hash, method)));
+                match copied {
+                    Ok((hash, method)) => self.copied(&self.target, hash, method, buffer, options, record),
+                    Err(err) => Action::Failed { reason: err.to_string() },
+                }
+            },
+        }
+    }
+
+    /// `target` has the content `hash` of the source.
+    fn link_to(&self, target: &Path, hash: String, options: &Options, record: &Recorder) -> Action {
+        if let Err(err) = record.verified(target, &hash) {
+            return Action::Failed { reason: err.to_string() };
+        }
+        if options.mode == Mode::Move {
+            return self.remove(target, hash, None, record);
+        }
+        match replace_with_link(&self.source, target, options) {
+            Ok(_) => Action::Linked { target: target.to_path_buf(), bytes: self.size },
+            Err(err) => Action::Failed { reason: err.to_string() },
+        }
+    }
+
+    fn copy_to(&self, target: &Path, buffer: &mut [u8], options: &Options, record: &Recorder) -> Action {
+        match copy_new_file(&self.source, target, buffer, options) {
+            Ok((hash, method)) => self.copied(target, hash, method, buffer, options, record),
+            Err(err) => Action::Failed { reason: err.to_string() },
+        }
+    }
+
+    /// The source has been copied to `target` and verified; the copy goes into the manifest right away.
+    /// In `Mode::Move` the source is deleted - if it still has the content of the copy.
+    /// A source that is kept fails the file, the copy stays in the target.
+    fn copied(&self, target: &Path, hash: String, method: CopyMethod, buffer: &mut [u8], options: &Options, record: &Recorder) -> Action {
+        if let Err(err) = record.copied(target, &hash) {
+            return Action::Failed { reason: format!("copied to '{}', but {}", target.display(), err) };
+        }
+        if options.mode != Mode::Move {
+            return Action::Copied { target: target.to_path_buf(), bytes: self.size, hash, method };
+        }
+        match hash_file(&self.source, buffer, options) {
+            Ok(source_hash) if source_hash == hash => self.remove(target, hash, Some(method), record),
+            Ok(_) => Action::Failed { reason: format!("copied to '{}', but the source changed meanwhile and is kept", target.display()) },
+            Err(err) => Action::Failed { reason: format!("copied to '{}', but the source is kept: {}", target.display(), err) },
+        }
+    }
+
+    /// Deletes the source, whose content `hash` is in `target`, and records that right away.
+    /// The source goes into the record with its absolute path - the record is kept in the archive,
+    /// where a path relative to the directory the run was started in means nothing.
+    fn remove(&self, target: &Path, hash: String, method: Option<CopyMethod>, record: &Recorder) -> Action {
+        let dir = match self.source.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let removed_path = match fs::canonicalize(dir) {
+            Ok(dir) => dir.join(self.source.file_name().unwrap_or_default()),
+            Err(err) => return Action::Failed { reason: format!("'{}' is in place, but the source is kept: {}", target.display(), err) },
+        };
+        if let Err(err) = fs::remove_file(&self.source) {
+            return Action::Failed { reason: format!("'{}' is in place, but the source is kept: {}", target.display(), err) };
+        }
+        if let Err(err) = record.removed(&removed_path, &hash) {
+            return Action::Failed { reason: format!("moved to '{}', but {}", target.display(), err) };
+        }
+        Action::Moved { target: target.to_path_buf(), bytes: self.size, hash, method }
+    }
+}
+
+/// `dir/sample.fastq.gz` becomes `dir/sample.v<version>.fastq.gz`, so the file still matches its patterns.
+fn versioned_path(path: &Path, version: usize) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let versioned = match name.char_indices().skip(1).find(|(_, c)| *c == '.') {
+        Some((i, _)) => format!("{}.v{}{}", &name[..i], version, &name[i..]),
+        None => format!("{}.v{}", name, version),
+    };
+    path.with_file_name(versioned)
+}
+
+fn fingerprint(metadata: &fs::Metadata) -> (u64, u64, u32) {
+    let mtime = metadata.modified().ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    (metadata.len(), mtime.as_secs(), mtime.subsec_nanos())
+}
+
+impl Plan {
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(self).map_err(|err| Error::InvalidPlan { path: self.source_folder.clone(), reason: err.to_string() })
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let text = fs::read_to_string(path).map_err(|err| Error::io(path, err))?;
+        serde_json::from_str(&text).map_err(|err| Error::InvalidPlan { path: path.to_path_buf(), reason: err.to_string() })
+    }
+}
+
+/// Computes what `copy_files_matching_patterns` would do without changing anything on disk.
+pub fn plan_copy(source_folder: &str, target_folder: &str, options: &Options) -> Result<Plan, Error> {
+    if let Err(err) = fs::metadata(source_folder) {
+        return Err(Error::io(Path::new(source_folder), err));
+    }
+    let mut entries = Vec::new();
+    for entry in walk_matching(source_folder, options) {
+        match entry {
+            Ok(entry) => {
+                let target_path = Path::new(target_folder).join(entry.path().strip_prefix(source_folder).unwrap());
+                entries.push(PlanEntry::new(entry.path(), &target_path));
+            },
+            Err(err) => {
+                let path = err.path().map(Path::to_path_buf).unwrap_or_default();
+                let mut skipped = PlanEntry::new(&path, &path);
+                skipped.step = Step::Skip;
+                skipped.reason = Some(err.to_string());
+                entries.push(skipped);
+            },
+        }
+    }
+    Ok(Plan { source_folder: source_folder.into(), target_folder: target_folder.into(), entries })
+}
+
+/// Executes exactly the steps of `plan`. The include and exclude patterns of `options` are not used -
+/// the plan decides which files are processed.
+/// Entries whose source changed in size or modification time since planning are refused.
+pub fn apply_plan(plan: &Plan, options: &Options) -> Result<RunReport, Error> {
+    let mut report = RunReport::new();
+    let record = Recorder::new(&plan.target_folder);
+
+    let actions = parallel::run(plan.entries.iter(), options.workers, |entry, buffer| {
+        match entry.step {
+            Step::Skip => entry.execute(buffer, options, &record),
+            _ => match entry.changed() {
+                Some(reason) => Action::Failed { reason },
+                None => entry.execute(buffer, options, &record),
+            },
+        }
+    });
+    for (entry, action) in plan.entries.iter().zip(actions) {
+        report.push(entry.source.clone(), action);
+    }
+    Ok(report)
+}
@@ -0,0 +1,195 @@
+//progress.rs
+
+use std::fmt;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{walk_matching, Options};
+
+/// What a worker is doing with the data of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Writing a copy (hashing the stream on the way).
+    Copying,
+    /// Reading a file only to hash it.
+    Hashing,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Phase::Copying => write!(f, "copying"),
+            Phase::Hashing => write!(f, "hashing"),
+        }
+    }
+}
+
+/// Reported to `Options::progress` while a run works through its files.
+#[derive(Debug, Clone, Copy)]
+pub enum ProgressEvent<'a> {
+    /// `bytes` more bytes of `path` have been copied or hashed.
+    Bytes { path: &'a Path, phase: Phase, bytes: u64 },
+    /// `path` is done - whatever happened to it. `bytes` is its size before the run.
+    FileDone { path: &'a Path, bytes: u64 },
+}
+
+/// A callback for `ProgressEvent`s. With several workers it is called from all of their threads.
+#[derive(Clone)]
+pub struct Progress(Arc<dyn Fn(ProgressEvent) + Send + Sync>);
+
+impl Progress {
+    pub fn new(callback: impl Fn(ProgressEvent) + Send + Sync + 'static) -> Self {
+        Progress(Arc::new(callback))
+    }
+
+    pub(crate) fn report(&self, event: ProgressEvent) {
+        (self.0)(event)
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Progress")
+    }
+}
+
+/// Counts the regular files below `folder` selected by `options` and their bytes,
+/// the work a `copy_files_matching_patterns` run has ahead of it.
+pub(crate) fn count_matching(folder: &str, options: &Options) -> (u64, u64) {
+    walk_matching(folder, options)
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .fold((0, 0), |(files, bytes), entry| (files + 1, bytes + entry.metadata().map(|m| m.len()).unwrap_or(0)))
+}
+
+/// How often a status line is written if stderr is not a terminal.
+const LOG_INTERVAL: Duration = Duration::from_secs(30);
+/// How often the progress line on a terminal is redrawn.
+const DRAW_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Shows the progress of a run on stderr: a line that is redrawn in place on a terminal,
+/// a status line every `LOG_INTERVAL` otherwise (e.g. in a cron log).
+pub(crate) struct Meter {
+    tty: bool,
+    state: Mutex<MeterState>,
+}
+
+struct MeterState {
+    started: Instant,
+    last_shown: Instant,
+    /// Whether a progress line is on the terminal.
+    drawn: bool,
+    files: u64,
+    total: Option<(u64, u64)>,
+    /// The size of the files done.
+    done_bytes: u64,
+    copied_bytes: u64,
+    /// Everything copied or hashed, the load on the disks.
+    read_bytes: u64,
+    current: Option<(PathBuf, Phase)>,
+}
+
+impl Meter {
+    pub(crate) fn new() -> Arc<Self> {
+        let now = Instant::now();
+        Arc::new(Meter {
+            tty: std::io::stderr().is_terminal(),
+            state: Mutex::new(MeterState {
+                started: now, last_shown: now, drawn: false, files: 0, total: None, done_bytes: 0, copied_bytes: 0, read_bytes: 0, current: None,
+            }),
+        })
+    }
+
+    /// Sets the number of files and bytes the run will process, for the ETA.
+    pub(crate) fn set_total(&self, files: u64, bytes: u64) {
+        if let Ok(mut state) = self.state.lock() {
+            state.total = Some((files, bytes));
+        }
+    }
+
+    /// The callback to put into `Options::progress`.
+    pub(crate) fn progress(self: &Arc<Self>) -> Progress {
+        let meter = Arc::clone(self);
+        Progress::new(move |event| meter.update(event))
+    }
+
+    fn update(&self, event: ProgressEvent) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        match event {
+            ProgressEvent::Bytes { path, phase, bytes } => {
+                state.read_bytes += bytes;
+                if phase == Phase::Copying {
+                    state.copied_bytes += bytes;
+                }
+                if state.current.as_ref().is_none_or(|(current, current_phase)| current != path || *current_phase != phase) {
+                    state.current = Some((path.to_path_buf(), phase));
+                }
+            },
+            ProgressEvent::FileDone { bytes, .. } => {
+                state.files += 1;
+                state.done_bytes += bytes;
+            },
+        }
+        let now = Instant::now();
+        let interval = if self.tty { DRAW_INTERVAL } else { LOG_INTERVAL };
+        if now.duration_since(state.last_shown) < interval {
+            return;
+        }
+        state.last_shown = now;
+        state.drawn = self.tty;
+        let line = state.line(now);
+        let mut stderr = std::io::stderr().lock();
+        let _ = if self.tty { write!(stderr, "\r\x1b[K{}", line) } else { writeln!(stderr, "Progress: {}", line) };
+        let _ = stderr.flush();
+    }
+
+    /// Clears the progress line before the report is printed.
+    pub(crate) fn finish(&self) {
+        if self.state.lock().map(|state| state.drawn).unwrap_or(false) {
+            eprint!("\r\x1b[K");
+        }
+    }
+}
+
+impl MeterState {
+    fn line(&self, now: Instant) -> String {
+        let elapsed = now.duration_since(self.started).as_secs_f64().max(0.001);
+        let mut line = match self.total {
+            Some((files, _)) => format!("{}/{} files", self.files, files),
+            None => format!("{} files", self.files),
+        };
+        line.push_str(&format!(", {} copied, {}/s", human_bytes(self.copied_bytes), human_bytes((self.read_bytes as f64 / elapsed) as u64)));
+        if let Some((_, total_bytes)) = self.total {
+            let rate = self.done_bytes as f64 / elapsed;
+            if rate > 0.0 {
+                let remaining = total_bytes.saturating_sub(self.done_bytes) as f64 / rate;
+                line.push_str(&format!(", ETA {}", human_duration(Duration::from_secs_f64(remaining))));
+            }
+        }
+        if let Some((path, phase)) = &self.current {
+            line.push_str(&format!(", {} {}", phase, path.file_name().unwrap_or(path.as_os_str()).to_string_lossy()));
+        }
+        line
+    }
+}
+
+fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 { format!("{} B", bytes) } else { format!("{:.1} {}", value, UNITS[unit]) }
+}
+
+fn human_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
@@ -0,0 +1,153 @@
+//rebase.rs
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{create_symlink, hash_file, link_target, parallel, relative_path, walk_error, walk_matching};
+use crate::{Action, Error, Manifest, Options, Pattern, RunReport};
+
+/// A link below the tree that points into the old location, and where it should point now.
+struct Rebase {
+    link: PathBuf,
+    new_target: PathBuf,
+    /// The hash the manifest lists for the new target, or why there is none.
+    expected: Result<String, String>,
+    relative: bool,
+}
+
+/// Repoints every symbolic link in `tree` that points below `from` to the same path below `to`.
+///
+/// A link is only changed if its new target exists and has the hash listed for it in the manifest
+/// of its archive (the nearest `SHA256SUMS` in a directory between `to` and the new target).
+/// Relative links stay relative. Links pointing elsewhere are not touched and not reported.
+/// Without any include pattern in `options` all links are checked.
+pub fn rebase_links(tree: &str, from: &Path, to: &Path, options: &Options) -> Result<RunReport, Error> {
+    if let Err(err) = fs::metadata(tree) {
+        return Err(Error::io(Path::new(tree), err));
+    }
+    // the links were made to canonicalized paths; the old location may be gone already
+    let absolute = |path: &Path| match fs::canonicalize(path) {
+        Ok(path) => Ok(path),
+        Err(_) => std::path::absolute(path).map(|path| normalize(&path)).map_err(|err| Error::io(path, err)),
+    };
+    let from = absolute(from)?;
+    let to = absolute(to)?;
+    let mut options = options.clone();
+    if options.include.is_empty() {
+        options.include.push(Pattern::suffix(""));
+    }
+    let mut report = RunReport::new();
+    let mut manifests = HashMap::new();
+
+    let mut rebases = Vec::new();
+    for entry in walk_matching(tree, &options) {
+        let entry = match entry {
+            Ok(entry) if entry.path_is_symlink() => entry,
+            Ok(_) => continue,
+            Err(err) => {
+                let (path, action) = walk_error(err);
+                report.push(path, action);
+                continue;
+            },
+        };
+        let link = entry.path();
+        let (old_target, relative) = match (fs::read_link(link), link_target(link)) {
+            (Ok(raw), Ok(resolved)) => (normalize(&std::path::absolute(&resolved).unwrap_or(resolved)), raw.is_relative()),
+            _ => continue,
+        };
+        let rest = match old_target.strip_prefix(&from) {
+            Ok(rest) => rest,
+            Err(_) => continue,
+        };
+        let new_target = to.join(rest);
+        let expected = expected_hash(&new_target, &to, &mut manifests);
+        rebases.push(Rebase { link: link.to_path_buf(), new_target, expected, relative });
+    }
+
+    let actions = parallel::run(rebases.into_iter(), options.workers, |rebase, buffer| {
+        let action = match rebase.apply(buffer, &options) {
+            Ok(()) => Action::Rebased { target: rebase.new_target },
+            Err(reason) => Action::Failed { reason },
+        };
+        (rebase.link, action)
+    });
+    for (path, action) in actions {
+        report.push(path, action);
+    }
+    Ok(report)
+}
+
+impl Rebase {
+    fn apply(&self, buffer: &mut [u8], options: &Options) -> Result<(), String> {
+        let expected = self.expected.as_ref().map_err(String::clone)?;
+        if !self.new_target.is_file() {
+            return Err(format!("the new target '{}' does not exist", self.new_target.display()));
+        }
+        let found = hash_file(&self.new_target, buffer, options).map_err(|err| err.to_string())?;
+        if &found != expected {
+            return Err(format!("the new target '{}' does not match its manifest (sha256 {} vs {})", self.new_target.display(), found, expected));
+        }
+        let link_target = if self.relative {
+            let dir = match self.link.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            let abs_dir = fs::canonicalize(dir).map_err(|err| Error::io(dir, err).to_string())?;
+            let abs_target = fs::canonicalize(&self.new_target).map_err(|err| Error::io(&self.new_target, err).to_string())?;
+            relative_path(&abs_dir, &abs_target)
+        } else {
+            self.new_target.clone()
+        };
+        replace_link(&self.link, &link_target).map_err(|err| err.to_string())
+    }
+}
+
+/// Creates the new link next to the old one and renames it over it - the old link stays if anything fails.
+fn replace_link(link: &Path, link_target: &Path) -> Result<(), Error> {
+    let name = link.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = link.with_file_name(format!(".{}.rebase", name));
+    if fs::symlink_metadata(&temp_path).is_ok() {
+        fs::remove_file(&temp_path).map_err(|err| Error::io(&temp_path, err))?;
+    }
+    if let Err(err) = create_symlink(link_target, &temp_path) {
+        return Err(Error::SymlinkFailed { link: temp_path, target: link_target.to_path_buf(), err });
+    }
+    if let Err(err) = fs::rename(&temp_path, link) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(Error::RenameFailed { from: temp_path, to: link.to_path_buf(), err });
+    }
+    Ok(())
+}
+
+/// The hash of `target` in the nearest manifest between `to` and `target` that lists it.
+fn expected_hash(target: &Path, to: &Path, manifests: &mut HashMap<PathBuf, Option<Manifest>>) -> Result<String, String> {
+    for root in target.ancestors().skip(1) {
+        if !root.starts_with(to) {
+            break;
+        }
+        let manifest = manifests.entry(root.to_path_buf()).or_insert_with(|| {
+            if Manifest::path(root).exists() { Manifest::load(root).ok() } else { None }
+        });
+        let relative_path = target.strip_prefix(root).unwrap_or(target);
+        if let Some(hash) = manifest.as_ref().and_then(|manifest| manifest.get(relative_path)) {
+            return Ok(hash.to_string());
+        }
+    }
+    Err(format!("'{}' is not listed in any manifest below '{}'", target.display(), to.display()))
+}
+
+/// Removes `.` and `..` from `path` without touching the file system - the old target usually does not exist any more.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => (),
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            },
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
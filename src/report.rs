@@ -0,0 +1,124 @@
+//report.rs
+
+use std::fmt;
+use std::path::PathBuf;
+use serde::Serialize;
+
+use crate::CopyMethod;
+
+/// What happened to one file during a run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    /// The file was copied to `target` using `method` and the copy was verified; `hash` is its sha256.
+    Copied { target: PathBuf, bytes: u64, hash: String, method: CopyMethod },
+    /// The file was replaced by a link to the already existing `target`.
+    Linked { target: PathBuf, bytes: u64 },
+    /// The file was deleted after its content was verified to be in `target`.
+    /// `method` is set if the copy was made by this run.
+    Moved { target: PathBuf, bytes: u64, hash: String, method: Option<CopyMethod> },
+    /// The link now points to `target` in the new location of the archive.
+    Rebased { target: PathBuf },
+    /// A link was replaced by a verified copy of `target` again (a hard link by a copy of itself).
+    Restored { target: PathBuf, bytes: u64 },
+    /// Nothing was done.
+    Skipped { reason: String },
+    /// Something went wrong - the file has not been touched or was restored.
+    Failed { reason: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileReport {
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+/// The outcome of a `copy_files_matching_patterns`, `revert_links` or `rebase_links` run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunReport {
+    pub files: Vec<FileReport>,
+    /// Bytes written into new copies.
+    pub bytes_copied: u64,
+    /// Bytes no longer stored twice as the originals were replaced by links or deleted.
+    pub bytes_freed: u64,
+}
+
+impl RunReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, path: PathBuf, action: Action) {
+        match &action {
+            Action::Copied { bytes, .. } | Action::Restored { bytes, .. } => self.bytes_copied += bytes,
+            Action::Linked { bytes, .. } => self.bytes_freed += bytes,
+            Action::Moved { bytes, method, .. } => {
+                if method.is_some() {
+                    self.bytes_copied += bytes;
+                }
+                self.bytes_freed += bytes;
+            },
+            _ => (),
+        }
+        self.files.push(FileReport { path, action });
+    }
+
+    pub fn failed(&self) -> usize {
+        self.files.iter().filter(|f| matches!(f.action, Action::Failed { .. })).count()
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.failed() > 0
+    }
+
+    /// The process exit code for this report: 0 if every file was handled, 3 if any file failed.
+    /// (1 is used by the binaries for fatal errors and 2 for usage errors.)
+    pub fn exit_code(&self) -> i32 {
+        if self.has_failures() { 3 } else { 0 }
+    }
+
+    pub fn count(&self, f: fn(&Action) -> bool) -> usize {
+        self.files.iter().filter(|r| f(&r.action)).count()
+    }
+}
+
+impl fmt::Display for FileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.action {
+            Action::Copied { target, method, .. } =>
+                write!(f, "Copied '{}' to '{}' ({})", self.path.display(), target.display(), method),
+            Action::Linked { target, .. } =>
+                write!(f, "Created link here '{}' linking to '{}'", self.path.display(), target.display()),
+            Action::Moved { target, method: Some(method), .. } =>
+                write!(f, "Moved '{}' to '{}' ({})", self.path.display(), target.display(), method),
+            Action::Moved { target, method: None, .. } =>
+                write!(f, "Removed '{}' - verified copy in '{}'", self.path.display(), target.display()),
+            Action::Rebased { target } =>
+                write!(f, "Repointed link '{}' to '{}'", self.path.display(), target.display()),
+            Action::Restored { target, .. } =>
+                write!(f, "Replaced link '{}' with a copy of '{}'", self.path.display(), target.display()),
+            Action::Skipped { reason } =>
+                write!(f, "Skipped '{}': {}", self.path.display(), reason),
+            Action::Failed { reason } =>
+                write!(f, "Failed '{}': {}", self.path.display(), reason),
+        }
+    }
+}
+
+impl fmt::Display for RunReport {
+    /// The summary line printed at the end of a run.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} copied, {} linked, {} moved, {} rebased, {} restored, {} skipped, {} failed; {} bytes copied, {} bytes freed",
+            self.count(|a| matches!(a, Action::Copied { .. })),
+            self.count(|a| matches!(a, Action::Linked { .. })),
+            self.count(|a| matches!(a, Action::Moved { .. })),
+            self.count(|a| matches!(a, Action::Rebased { .. })),
+            self.count(|a| matches!(a, Action::Restored { .. })),
+            self.count(|a| matches!(a, Action::Skipped { .. })),
+            self.failed(),
+            self.bytes_copied,
+            self.bytes_freed,
+        )
+    }
+}
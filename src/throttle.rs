@@ -0,0 +1,153 @@
+//throttle.rs
+
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::Error;
+
+/// A limit on the bytes and IO operations per second shared by all workers of a run,
+/// so a copy to or from shared storage leaves some of its bandwidth to everybody else.
+///
+/// The rate counts the bytes read - by a copy and by every hash, also the re-reads that verify a copy.
+/// The bytes written are not counted again. The operations are the reads and the writes.
+#[derive(Debug)]
+pub struct Throttle {
+    /// Bytes read per second.
+    max_rate: Option<u64>,
+    /// Reads and writes per second.
+    max_iops: Option<u64>,
+    /// The limits only apply during these hours; all the time if `None`.
+    hours: Option<Hours>,
+    budget: Mutex<Budget>,
+}
+
+/// The bytes that may still be read and the operations that may still be done right now;
+/// negative if the workers are ahead of the limits.
+#[derive(Debug)]
+struct Budget {
+    bytes: f64,
+    ops: f64,
+    updated: Instant,
+}
+
+impl Throttle {
+    pub fn new(max_rate: Option<u64>, max_iops: Option<u64>, hours: Option<Hours>) -> Self {
+        Throttle {
+            max_rate: max_rate.filter(|rate| *rate > 0),
+            max_iops: max_iops.filter(|iops| *iops > 0),
+            hours,
+            budget: Mutex::new(Budget { bytes: 0.0, ops: 0.0, updated: Instant::now() }),
+        }
+    }
+
+    /// Accounts for `bytes` read in `ops` reads and writes together and sleeps as long as
+    /// the workers are ahead of the limits. At most one second worth of unused budget is saved up.
+    pub(crate) fn take(&self, bytes: u64, ops: u64) {
+        if self.hours.is_some_and(|hours| !hours.contains(local_minute_of_day())) {
+            return;
+        }
+        let wait = {
+            let mut budget = match self.budget.lock() {
+                Ok(budget) => budget,
+                Err(_) => return,
+            };
+            let now = Instant::now();
+            let elapsed = now.duration_since(budget.updated).as_secs_f64();
+            budget.updated = now;
+            let mut wait: f64 = 0.0;
+            if let Some(rate) = self.max_rate {
+                budget.bytes = (budget.bytes + elapsed * rate as f64).min(rate as f64) - bytes as f64;
+                wait = wait.max(-budget.bytes / rate as f64);
+            }
+            if let Some(iops) = self.max_iops {
+                budget.ops = (budget.ops + elapsed * iops as f64).min(iops as f64) - ops as f64;
+                wait = wait.max(-budget.ops / iops as f64);
+            }
+            wait
+        };
+        // the debt stays in the budget, so the next caller waits for it as well
+        if wait > 0.0 {
+            thread::sleep(Duration::from_secs_f64(wait));
+        }
+    }
+}
+
+/// Parses a rate like `200M`: bytes per second with an optional `K`, `M`, `G` or `T` (powers of 1024).
+pub fn parse_rate(rate: &str) -> Result<u64, Error> {
+    let invalid = || Error::InvalidOption { option: "--max-rate".to_string(), reason: format!("'{}' is not a rate like 500K, 200M or 1G", rate) };
+    let digits = rate.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let factor: u64 = match rate[digits.len()..].to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(invalid()),
+    };
+    match digits.parse::<u64>() {
+        Ok(value) if value > 0 => value.checked_mul(factor).ok_or_else(invalid),
+        _ => Err(invalid()),
+    }
+}
+
+/// A daily time window in local time like `08:00-18:00`; `22:00-06:00` spans midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hours {
+    /// Minutes after midnight.
+    from: u32,
+    to: u32,
+}
+
+impl Hours {
+    /// Whether the window contains the minute `minute` after midnight.
+    pub fn contains(&self, minute: u32) -> bool {
+        if self.from <= self.to {
+            self.from <= minute && minute < self.to
+        } else {
+            minute >= self.from || minute < self.to
+        }
+    }
+}
+
+impl std::str::FromStr for Hours {
+    type Err = Error;
+
+    fn from_str(hours: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidOption { option: "--limit-hours".to_string(), reason: format!("'{}' is not a time window like 08:00-18:00", hours) };
+        let minute = |time: &str| -> Option<u32> {
+            let (hour, minute) = time.trim().split_once(':')?;
+            let (hour, minute) = (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?);
+            (hour <= 24 && minute < 60 && hour * 60 + minute <= 24 * 60).then_some(hour * 60 + minute)
+        };
+        let (from, to) = hours.split_once('-').ok_or_else(invalid)?;
+        match (minute(from), minute(to)) {
+            (Some(from), Some(to)) if from != to => Ok(Hours { from, to }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// The minutes since midnight in local time.
+#[cfg(unix)]
+fn local_minute_of_day() -> u32 {
+    // SAFETY: localtime_r only writes to the tm it is given
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            return utc_minute_of_day();
+        }
+        (tm.tm_hour * 60 + tm.tm_min) as u32
+    }
+}
+
+#[cfg(not(unix))]
+fn local_minute_of_day() -> u32 {
+    utc_minute_of_day()
+}
+
+fn utc_minute_of_day() -> u32 {
+    let seconds = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    (seconds % 86_400 / 60) as u32
+}
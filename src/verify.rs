@@ -0,0 +1,139 @@
+//verify.rs
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+
+use crate::{hash_file, parallel, walk_matching};
+use crate::{Error, Manifest, Options, Pattern, ProgressEvent, MANIFEST_NAME, REMOVED_NAME};
+
+/// The state of one file of the archive compared to the manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "check", rename_all = "snake_case")]
+pub enum Check {
+    /// The file still has the hash from the manifest.
+    Ok,
+    /// The file is listed in the manifest but is gone.
+    Missing,
+    /// The file has a different hash than listed in the manifest.
+    Modified { expected: String, found: String },
+    /// The file is not listed in the manifest.
+    Unlisted,
+    /// The file could not be read.
+    Failed { reason: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileCheck {
+    /// The path relative to the target folder.
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub check: Check,
+}
+
+/// The result of `verify_archive`, sorted by path.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    pub files: Vec<FileCheck>,
+}
+
+impl VerifyReport {
+    pub fn count(&self, f: fn(&Check) -> bool) -> usize {
+        self.files.iter().filter(|file| f(&file.check)).count()
+    }
+
+    /// Missing, modified or unreadable files.
+    pub fn has_problems(&self) -> bool {
+        self.count(|check| matches!(check, Check::Missing | Check::Modified { .. } | Check::Failed { .. })) > 0
+    }
+
+    /// 0 if every listed file is intact, 3 otherwise. Unlisted files alone do not fail the check.
+    pub fn exit_code(&self) -> i32 {
+        if self.has_problems() { 3 } else { 0 }
+    }
+}
+
+impl fmt::Display for FileCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.check {
+            Check::Ok => write!(f, "{}: OK", self.path.display()),
+            Check::Missing => write!(f, "{}: MISSING", self.path.display()),
+            Check::Modified { expected, found } =>
+                write!(f, "{}: MODIFIED (expected {}, found {})", self.path.display(), expected, found),
+            Check::Unlisted => write!(f, "{}: UNLISTED", self.path.display()),
+            Check::Failed { reason } => write!(f, "{}: FAILED {}", self.path.display(), reason),
+        }
+    }
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ok, {} missing, {} modified, {} unlisted, {} failed",
+            self.count(|check| matches!(check, Check::Ok)),
+            self.count(|check| matches!(check, Check::Missing)),
+            self.count(|check| matches!(check, Check::Modified { .. })),
+            self.count(|check| matches!(check, Check::Unlisted)),
+            self.count(|check| matches!(check, Check::Failed { .. })),
+        )
+    }
+}
+
+/// Re-hashes the files in `target_folder` and compares them to its manifest.
+/// Only files selected by `options` are checked - without any include pattern the whole archive is.
+pub fn verify_archive(target_folder: &str, options: &Options) -> Result<VerifyReport, Error> {
+    if let Err(err) = fs::metadata(target_folder) {
+        return Err(Error::io(Path::new(target_folder), err));
+    }
+    let manifest = Manifest::load(Path::new(target_folder))?;
+    let mut options = options.clone();
+    if options.include.is_empty() {
+        options.include.push(Pattern::suffix(""));
+    }
+    for name in [MANIFEST_NAME, REMOVED_NAME] {
+        options.exclude.push(Pattern::regex(&format!("^{}$", regex::escape(name)))?);
+    }
+
+    // relative path -> (hash in the manifest, exists on disk)
+    let mut files: BTreeMap<PathBuf, (Option<String>, bool)> = BTreeMap::new();
+    let mut unreadable = Vec::new();
+    for (relative_path, hash) in manifest.iter() {
+        if options.selects(relative_path) {
+            files.insert(relative_path.clone(), (Some(hash.clone()), false));
+        }
+    }
+    for entry in walk_matching(target_folder, &options) {
+        match entry {
+            Ok(entry) => {
+                let relative_path = entry.path().strip_prefix(target_folder).unwrap_or(entry.path());
+                files.entry(relative_path.to_path_buf()).or_insert((None, false)).1 = true;
+            },
+            Err(err) => {
+                let path = err.path().map(Path::to_path_buf).unwrap_or_default();
+                let path = path.strip_prefix(target_folder).map(Path::to_path_buf).unwrap_or(path);
+                unreadable.push(FileCheck { path, check: Check::Failed { reason: err.to_string() } });
+            },
+        }
+    }
+
+    let checks = parallel::run(files.into_iter(), options.workers, |(path, (expected, exists)), buffer| {
+        let check = match (expected, exists) {
+            (None, _) => Check::Unlisted,
+            (Some(_), false) => Check::Missing,
+            (Some(expected), true) => match hash_file(&Path::new(target_folder).join(&path), buffer, &options) {
+                Ok(found) if found == expected => Check::Ok,
+                Ok(found) => Check::Modified { expected, found },
+                Err(err) => Check::Failed { reason: err.to_string() },
+            },
+        };
+        let bytes = fs::metadata(Path::new(target_folder).join(&path)).map(|m| m.len()).unwrap_or(0);
+        options.report_progress(ProgressEvent::FileDone { path: &path, bytes });
+        FileCheck { path, check }
+    });
+
+    let mut report = VerifyReport { files: checks };
+    report.files.extend(unreadable);
+    report.files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(report)
+}
@@ -0,0 +1,123 @@
+//cli_test.rs
+
+#[cfg(test)]
+mod tests {
+
+    use tempdir::TempDir;
+    use file_copy_tool::cli;
+    use std::fs;
+
+    fn is_link(path: &std::path::Path) -> bool {
+        fs::symlink_metadata(path).unwrap().file_type().is_symlink()
+    }
+
+    #[test]
+    fn test_usage_errors() {
+        assert_eq!(cli::run(["file_copy_tool"]), 2);
+        assert_eq!(cli::run(["file_copy_tool", "copy", "a", "b"]), 2);
+        assert_eq!(cli::run(["file_copy_tool", "copy", "-j", "0", "a", "b", ".txt"]), 2);
+        assert_eq!(cli::run(["file_copy_tool", "verify", "a", "--on-conflict", "skip"]), 2);
+        assert_eq!(cli::run(["file_copy_tool", "apply", "--exclude", "glob:tmp*", "plan.json"]), 2);
+        assert_eq!(cli::run(["file_copy_tool", "a", "b", "re:("]), 2);
+        assert_eq!(cli::run(["file_copy_tool", "copy", "--max-rate", "0", "a", "b", ".txt"]), 2);
+        assert_eq!(cli::run(["file_copy_tool", "copy", "--max-rate", "200M", "--limit-hours", "8-18", "a", "b", ".txt"]), 2);
+        assert_eq!(cli::run(["file_copy_tool", "copy", "--limit-hours", "08:00-18:00", "a", "b", ".txt"]), 2);
+        assert_eq!(cli::run(["file_copy_tool", "--help"]), 0);
+        // a fatal error, not a usage error
+        assert_eq!(cli::run(["file_copy_tool", "copy", "/does/not/exist", "b", ".txt"]), 1);
+    }
+
+    #[test]
+    fn test_commands() {
+        let tmp_dir = TempDir::new("cli_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.txt"), "Test data").unwrap();
+        fs::write(source.join("b.txt"), "Test content").unwrap();
+        let (source_folder, target_folder) = (source.to_str().unwrap(), target.to_str().unwrap());
+
+        assert_eq!(cli::run(["file_copy_tool", "copy", source_folder, target_folder, "a.txt"]), 0);
+        assert_eq!(cli::run(["file_copy_tool", "copy", source_folder, target_folder, "a.txt"]), 0);
+        assert!(!is_link(&source.join("a.txt")));
+
+        assert_eq!(cli::run(["file_copy_tool", "link", source_folder, target_folder, ".txt"]), 0);
+        assert!(is_link(&source.join("a.txt")));
+        assert!(!target.join("b.txt").exists());
+        assert_eq!(cli::run(["file_copy_tool", "status", source_folder, target_folder, ".txt"]), 0);
+
+        // the classic call without a command
+        assert_eq!(cli::run(["file_copy_tool", "--workers", "2", source_folder, target_folder, ".txt"]), 0);
+        assert!(target.join("b.txt").exists());
+        assert_eq!(cli::run(["file_copy_tool", "verify", target_folder]), 0);
+
+        assert_eq!(cli::run(["file_copy_tool", "revert", source_folder, ".txt"]), 0);
+        assert!(!is_link(&source.join("a.txt")));
+        assert_eq!(fs::read_to_string(source.join("a.txt")).unwrap(), "Test data");
+    }
+
+    #[test]
+    fn test_run_jobs() {
+        let tmp_dir = TempDir::new("cli_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.txt"), "Test data").unwrap();
+        let jobs_file = tmp_dir.path().join("jobs.toml");
+        fs::write(&jobs_file, format!("[[job]]\nname = \"texts\"\nsource = \"{}\"\ntarget = \"{}\"\ninclude = [\".txt\"]\n",
+            source.display(), target.display())).unwrap();
+        let jobs = jobs_file.to_str().unwrap();
+
+        assert_eq!(cli::run(["file_copy_tool", "run", jobs, "other"]), 2);
+        assert_eq!(cli::run(["file_copy_tool", "run", jobs, "texts"]), 0);
+        assert!(target.join("a.txt").exists());
+        assert_eq!(cli::run(["file_copy_tool", "run", jobs]), 0);
+        assert!(is_link(&source.join("a.txt")));
+        assert_eq!(cli::run(["file_copy_tool", "run", "/does/not/exist.toml"]), 1);
+    }
+
+    #[test]
+    fn test_output_options() {
+        let tmp_dir = TempDir::new("cli_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.txt"), "Test data").unwrap();
+        let (source_folder, target_folder) = (source.to_str().unwrap(), target.to_str().unwrap());
+
+        assert_eq!(cli::run(["file_copy_tool", "-q", "-v", "copy", source_folder, target_folder, ".txt"]), 2);
+        assert_eq!(cli::run(["file_copy_tool", "--output", "xml", "copy", source_folder, target_folder, ".txt"]), 2);
+        // the global options go before or after the command - or before the classic call without one
+        assert_eq!(cli::run(["file_copy_tool", "-q", "--output", "json", "copy", source_folder, target_folder, ".txt"]), 0);
+        assert!(target.join("a.txt").exists());
+        assert_eq!(cli::run(["file_copy_tool", "-vv", "--output=json", source_folder, target_folder, ".txt"]), 0);
+        assert!(is_link(&source.join("a.txt")));
+        assert_eq!(cli::run(["file_copy_tool", "verify", target_folder, "--output", "json", "--quiet"]), 0);
+    }
+
+    #[test]
+    fn test_migrate_delete_old() {
+        let tmp_dir = TempDir::new("cli_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let old = tmp_dir.path().join("old");
+        let new = tmp_dir.path().join("new");
+        fs::create_dir_all(source.join("tmp")).unwrap();
+        fs::write(source.join("a.txt"), "Test data").unwrap();
+        fs::write(source.join("tmp/b.txt"), "Test content").unwrap();
+        let (source_folder, old_folder, new_folder) = (source.to_str().unwrap(), old.to_str().unwrap(), new.to_str().unwrap());
+        assert_eq!(cli::run(["file_copy_tool", "archive", "--move", source_folder, old_folder, ".txt"]), 0);
+        assert!(!source.join("tmp/b.txt").exists());
+
+        // the old archive is the only copy of tmp/b.txt - a filtered migration must not delete it
+        assert_eq!(cli::run(["file_copy_tool", "migrate", "--exclude-dir", "tmp", "--delete-old", "--no-links", old_folder, new_folder]), 2);
+        assert_eq!(cli::run(["file_copy_tool", "migrate", "--exclude", "glob:b.txt", "--delete-old", "--no-links", old_folder, new_folder]), 2);
+        // without links to repoint it has to be said so
+        assert_eq!(cli::run(["file_copy_tool", "migrate", "--delete-old", old_folder, new_folder]), 2);
+        assert_eq!(cli::run(["file_copy_tool", "migrate", "--no-links", old_folder, new_folder, source_folder]), 2);
+        assert!(old.join("tmp/b.txt").exists());
+
+        assert_eq!(cli::run(["file_copy_tool", "migrate", "--delete-old", "--no-links", old_folder, new_folder]), 0);
+        assert!(!old.exists());
+        assert_eq!(fs::read_to_string(new.join("tmp/b.txt")).unwrap(), "Test content");
+    }
+}
@@ -0,0 +1,81 @@
+//conflict_test.rs
+
+#[cfg(test)]
+mod tests {
+
+    use tempdir::TempDir;
+    use file_copy_tool::*;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    fn is_link(path: &Path) -> bool {
+        fs::symlink_metadata(path).unwrap().file_type().is_symlink()
+    }
+
+    /// a.fastq.gz is in the archive, then a different file shows up at the same path in the source
+    fn setup(tmp_dir: &TempDir) -> (PathBuf, PathBuf, Options) {
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.fastq.gz"), "old run").unwrap();
+        let options = Options::new(vec![Pattern::suffix(".fastq.gz")]);
+        copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        fs::write(source.join("a.fastq.gz"), "new run").unwrap();
+        (source, target, options)
+    }
+
+    #[test]
+    fn test_conflict_skip() {
+        let tmp_dir = TempDir::new("conflict_skip").unwrap();
+        let (source, target, options) = setup(&tmp_dir);
+
+        let report = copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert!(matches!(&report.files[0].action, Action::Skipped { reason } if reason.starts_with("conflict")), "{:?}", report);
+        assert!(!is_link(&source.join("a.fastq.gz")));
+        assert_eq!(fs::read_to_string(source.join("a.fastq.gz")).unwrap(), "new run");
+        assert_eq!(fs::read_to_string(target.join("a.fastq.gz")).unwrap(), "old run");
+    }
+
+    #[test]
+    fn test_conflict_keep_both() {
+        let tmp_dir = TempDir::new("conflict_keep").unwrap();
+        let (source, target, mut options) = setup(&tmp_dir);
+        options.conflict = ConflictPolicy::KeepBoth;
+
+        let report = copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert!(matches!(report.files[0].action, Action::Copied { .. }), "{:?}", report);
+        assert_eq!(fs::read_to_string(target.join("a.v1.fastq.gz")).unwrap(), "new run");
+        assert_eq!(fs::read_to_string(target.join("a.fastq.gz")).unwrap(), "old run");
+        assert!(Manifest::load(&target).unwrap().get(Path::new("a.v1.fastq.gz")).is_some());
+
+        // the next run finds the versioned copy and links to it
+        let report = copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert!(matches!(&report.files[0].action, Action::Linked { target: t, .. } if t.ends_with("a.v1.fastq.gz")), "{:?}", report);
+        assert!(is_link(&source.join("a.fastq.gz")));
+        assert_eq!(fs::read_to_string(source.join("a.fastq.gz")).unwrap(), "new run");
+        assert!(!target.join("a.v2.fastq.gz").exists());
+    }
+
+    #[test]
+    fn test_conflict_overwrite() {
+        let tmp_dir = TempDir::new("conflict_overwrite").unwrap();
+        let (source, target, mut options) = setup(&tmp_dir);
+        options.conflict = ConflictPolicy::Overwrite;
+
+        let report = copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert!(matches!(report.files[0].action, Action::Copied { .. }), "{:?}", report);
+        assert_eq!(fs::read_to_string(target.join("a.fastq.gz")).unwrap(), "new run");
+        assert!(!is_link(&source.join("a.fastq.gz")));
+
+        // the manifest knows the new content - and only that
+        let report = verify_archive(target.to_str().unwrap(), &Options::default()).unwrap();
+        assert_eq!(report.exit_code(), 0, "{:?}", report);
+        let manifest = fs::read_to_string(target.join(MANIFEST_NAME)).unwrap();
+        let new_hash = calculate_sha256(&target.join("a.fastq.gz"), &mut [0; 1024]).unwrap();
+        assert_eq!(manifest, format!("{}  a.fastq.gz\n", new_hash));
+        assert!(!target.join(".SHA256SUMS.partial").exists());
+
+        copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert!(is_link(&source.join("a.fastq.gz")));
+    }
+}
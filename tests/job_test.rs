@@ -0,0 +1,80 @@
+//job_test.rs
+
+#[cfg(test)]
+mod tests {
+
+    use tempdir::TempDir;
+    use file_copy_tool::*;
+    use std::fs;
+
+    #[test]
+    fn test_load_and_run_jobs() {
+        let tmp_dir = TempDir::new("job_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        fs::create_dir_all(source.join("tmp")).unwrap();
+        fs::write(source.join("a.txt"), "Test data").unwrap();
+        fs::write(source.join("tmp/b.txt"), "Test content").unwrap();
+        fs::write(source.join("c.log"), "log").unwrap();
+        let jobs_file = tmp_dir.path().join("jobs.toml");
+        fs::write(&jobs_file, format!(r#"
+[[job]]
+name = "texts"
+source = "{source}"
+target = "{target}"
+include = [".txt"]
+exclude_dirs = ["glob:tmp"]
+mode = "copy-only"
+
+[[job]]
+name = "logs"
+source = "{source}"
+target = "{target}"
+include = ["glob:*.log"]
+links = "relative"
+workers = 2
+max_rate = "1G"
+limit_hours = "22:00-06:00"
+"#, source = source.display(), target = target.display())).unwrap();
+
+        let jobs = load_jobs(&jobs_file).unwrap();
+        assert_eq!(jobs.iter().map(|job| job.name.as_str()).collect::<Vec<_>>(), ["texts", "logs"]);
+        assert_eq!(jobs[0].options.mode, Mode::CopyOnly);
+        assert_eq!(jobs[1].options.links, LinkStyle::Relative);
+        assert_eq!(jobs[1].options.workers, 2);
+        assert!(jobs[0].options.throttle.is_none() && jobs[1].options.throttle.is_some());
+
+        // the second run links what the first one copied
+        for _ in 0..2 {
+            for job in &jobs {
+                let report = run_job(job).unwrap();
+                assert_eq!(report.exit_code(), 0, "{:?}", report);
+            }
+        }
+        assert!(target.join("a.txt").exists());
+        assert!(!target.join("tmp/b.txt").exists());
+        assert!(!fs::symlink_metadata(source.join("a.txt")).unwrap().file_type().is_symlink());
+        assert!(fs::read_link(source.join("c.log")).unwrap().is_relative());
+        assert_eq!(fs::read_to_string(source.join("c.log")).unwrap(), "log");
+    }
+
+    #[test]
+    fn test_invalid_job_files() {
+        let tmp_dir = TempDir::new("job_test").unwrap();
+        let jobs_file = tmp_dir.path().join("jobs.toml");
+        let job = |name: &str, extra: &str| format!("[[job]]\nname = \"{}\"\nsource = \"a\"\ntarget = \"b\"\ninclude = [\".txt\"]\n{}\n", name, extra);
+
+        for text in [
+            job("a", "") + &job("a", ""),
+            job("a", "compress = true"),
+            job("a", "exclude = [\"re:(\"]"),
+            job("a", "mode = \"sideways\""),
+            job("a", "max_rate = \"fast\""),
+            job("a", "limit_hours = \"08:00-18:00\""),
+            "[[job]]\nname = \"a\"\nsource = \"a\"\ntarget = \"b\"\ninclude = []\n".to_string(),
+        ] {
+            fs::write(&jobs_file, &text).unwrap();
+            assert!(matches!(load_jobs(&jobs_file), Err(Error::InvalidJobFile { .. })), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,37 @@
+//legacy_cli_test.rs
+
+// its own test binary: the test changes the current directory of the process
+#[cfg(test)]
+mod tests {
+
+    use tempdir::TempDir;
+    use file_copy_tool::cli;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_source_folder_named_like_a_command() {
+        let tmp_dir = TempDir::new("legacy_cli_test").unwrap();
+        env::set_current_dir(tmp_dir.path()).unwrap();
+        for folder in ["copy", "link", "status"] {
+            fs::create_dir_all(folder).unwrap();
+            fs::write(format!("{}/a.bam", folder), "Test data").unwrap();
+        }
+
+        // a script written before there were commands
+        assert_eq!(cli::run(["file_copy_tool", "copy", "backup", ".bam"]), 0);
+        assert_eq!(fs::read_to_string("backup/a.bam").unwrap(), "Test data");
+        // the command would not find its source folder
+        assert_eq!(cli::run(["file_copy_tool", "link", "backup_link", ".bam", ".bai"]), 0);
+        assert!(fs::metadata("backup_link/a.bam").is_ok());
+
+        // 'status backup .bam .bai' reads both ways - refused
+        assert_eq!(cli::run(["file_copy_tool", "status", "backup", ".bam", ".bai"]), 2);
+        assert!(fs::metadata(".bam").is_err());
+        assert_eq!(cli::run(["file_copy_tool", "archive", "status", "backup", ".bam", ".bai"]), 0);
+        assert_eq!(fs::read_to_string("backup/a.bam").unwrap(), "Test data");
+
+        // the commands themselves still work here
+        assert_eq!(cli::run(["file_copy_tool", "verify", "backup"]), 0);
+    }
+}
@@ -0,0 +1,116 @@
+//manifest_test.rs
+
+#[cfg(test)]
+mod tests {
+
+    use tempdir::TempDir;
+    use file_copy_tool::*;
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_manifest_is_appended() {
+        let tmp_dir = TempDir::new("manifest_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::write(source.join("a.txt"), "Test data").unwrap();
+        fs::write(source.join("sub/b\\c.txt"), "Test content").unwrap();
+        let options = Options::new(vec![Pattern::suffix(".txt")]);
+
+        copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        let manifest = Manifest::load(&target).unwrap();
+        assert_eq!(manifest.len(), 2);
+        assert_eq!(manifest.get(Path::new("a.txt")), Some("e27c8214be8b7cf5bccc7c08247e3cb0c1514a48ee1f63197fe4ef3ef51d7e6f"));
+        assert!(manifest.get(Path::new("sub/b\\c.txt")).is_some());
+
+        // a second run links instead of copying and adds nothing
+        let before = fs::read_to_string(target.join(MANIFEST_NAME)).unwrap();
+        copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert_eq!(fs::read_to_string(target.join(MANIFEST_NAME)).unwrap(), before);
+
+        fs::write(source.join("c.txt"), "more").unwrap();
+        copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert_eq!(Manifest::load(&target).unwrap().len(), 3);
+
+        // the archive can be checked without this tool
+        if let Ok(output) = Command::new("sha256sum").arg("-c").arg(MANIFEST_NAME).current_dir(&target).output() {
+            assert!(output.status.success(), "sha256sum -c failed: {}", String::from_utf8_lossy(&output.stdout));
+        }
+    }
+
+    #[test]
+    fn test_unlisted_copy_is_listed_when_linked() {
+        let tmp_dir = TempDir::new("manifest_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.txt"), "Test data").unwrap();
+        fs::write(source.join("b.txt"), "Test content").unwrap();
+        let options = Options::new(vec![Pattern::suffix(".txt")]);
+        copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+
+        // a run killed after a.txt was in place but before it was listed
+        let manifest = fs::read_to_string(target.join(MANIFEST_NAME)).unwrap();
+        fs::write(target.join(MANIFEST_NAME), manifest.lines().filter(|line| !line.ends_with("a.txt")).map(|line| format!("{}\n", line)).collect::<String>()).unwrap();
+        assert!(Manifest::load(&target).unwrap().get(Path::new("a.txt")).is_none());
+
+        // the link step hashed the copy anyway and lists it again - b.txt only once
+        let report = copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert!(report.files.iter().all(|f| matches!(f.action, Action::Linked { .. })), "{:?}", report);
+        assert_eq!(fs::read_to_string(target.join(MANIFEST_NAME)).unwrap().lines().count(), 2);
+        let report = verify_archive(target.to_str().unwrap(), &Options::default()).unwrap();
+        assert!(report.files.iter().all(|f| f.check == Check::Ok), "{:?}", report);
+    }
+
+    #[test]
+    fn test_invalid_manifest() {
+        let tmp_dir = TempDir::new("bad_manifest_test").unwrap();
+        fs::write(tmp_dir.path().join(MANIFEST_NAME), "not a hash  file.txt\n").unwrap();
+        assert!(matches!(Manifest::load(tmp_dir.path()), Err(Error::InvalidManifest { line: 1, .. })));
+    }
+
+    #[test]
+    fn test_verify_archive() {
+        let tmp_dir = TempDir::new("verify_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        fs::create_dir_all(source.join("sub")).unwrap();
+        for name in ["a.txt", "b.txt", "c.txt", "sub/d.txt"] {
+            fs::write(source.join(name), name).unwrap();
+        }
+        copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &Options::new(vec![Pattern::suffix(".txt")])).unwrap();
+
+        let report = verify_archive(target.to_str().unwrap(), &Options::default()).unwrap();
+        assert_eq!(report.files.len(), 4, "{:?}", report);
+        assert!(report.files.iter().all(|f| f.check == Check::Ok), "{:?}", report);
+        assert_eq!(report.exit_code(), 0);
+
+        // silent corruption, a lost file and a stray file
+        fs::write(target.join("a.txt"), "bit rot").unwrap();
+        fs::remove_file(target.join("b.txt")).unwrap();
+        fs::write(target.join("e.txt"), "never copied by us").unwrap();
+
+        let report = verify_archive(target.to_str().unwrap(), &Options::default()).unwrap();
+        let check = |name: &str| report.files.iter().find(|f| f.path == Path::new(name)).map(|f| f.check.clone());
+        assert!(matches!(check("a.txt"), Some(Check::Modified { .. })), "{:?}", report);
+        assert_eq!(check("b.txt"), Some(Check::Missing));
+        assert_eq!(check("c.txt"), Some(Check::Ok));
+        assert_eq!(check("e.txt"), Some(Check::Unlisted));
+        assert_eq!(check(MANIFEST_NAME), None);
+        assert_eq!(report.exit_code(), 3);
+
+        // only a subset
+        let mut options = Options::new(vec![Pattern::parse("glob:sub/*").unwrap()]);
+        let report = verify_archive(target.to_str().unwrap(), &options).unwrap();
+        assert_eq!(report.files.len(), 1, "{:?}", report);
+        assert_eq!(report.exit_code(), 0);
+
+        options.include = vec![Pattern::suffix(".txt")];
+        options.exclude_dirs = vec![Pattern::parse("glob:sub").unwrap()];
+        let report = verify_archive(target.to_str().unwrap(), &options).unwrap();
+        assert_eq!(report.files.len(), 4, "{:?}", report);
+    }
+}
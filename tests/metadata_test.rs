@@ -0,0 +1,88 @@
+//metadata_test.rs
+
+#[cfg(all(test, unix))]
+mod tests {
+
+    use tempdir::TempDir;
+    use file_copy_tool::*;
+    use filetime::FileTime;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+
+    fn times(path: &Path) -> (FileTime, FileTime) {
+        let metadata = fs::metadata(path).unwrap();
+        (FileTime::from_last_access_time(&metadata), FileTime::from_last_modification_time(&metadata))
+    }
+
+    #[test]
+    fn test_preserve_metadata() {
+        let tmp_dir = TempDir::new("metadata_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        fs::create_dir_all(&source).unwrap();
+        let file = source.join("a.bam");
+        fs::write(&file, "Test data").unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o640)).unwrap();
+        filetime::set_file_times(&file, FileTime::from_unix_time(1_000_000, 0), FileTime::from_unix_time(2_000_000, 500)).unwrap();
+        // not every file system used for tests knows extended attributes
+        let has_xattrs = xattr::set(&file, "user.provenance", b"run 42").is_ok();
+        let expected_times = times(&file);
+
+        let mut options = Options::new(vec![Pattern::suffix(".bam")]);
+        options.preserve = Preserve::all();
+        copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+
+        let copy = target.join("a.bam");
+        assert_eq!(times(&copy), expected_times);
+        assert_eq!(fs::metadata(&copy).unwrap().permissions().mode() & 0o7777, 0o640);
+        if has_xattrs {
+            assert_eq!(xattr::get(&copy, "user.provenance").unwrap(), Some(b"run 42".to_vec()));
+        }
+
+        // the restored file gets the metadata of the archived copy
+        copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert!(fs::symlink_metadata(&file).unwrap().file_type().is_symlink());
+        revert_links(source.to_str().unwrap(), &options).unwrap();
+        assert!(!fs::symlink_metadata(&file).unwrap().file_type().is_symlink());
+        assert_eq!(times(&file).1, expected_times.1);
+        if has_xattrs {
+            assert_eq!(xattr::get(&file, "user.provenance").unwrap(), Some(b"run 42".to_vec()));
+        }
+    }
+
+    #[test]
+    fn test_preserve_read_only_source() {
+        let tmp_dir = TempDir::new("metadata_read_only_test").unwrap();
+        let file = tmp_dir.path().join("a.fastq.gz");
+        let copy = tmp_dir.path().join("copy.fastq.gz");
+        fs::write(&file, "Test data").unwrap();
+        let has_xattrs = xattr::set(&file, "user.provenance", b"run 42").is_ok();
+        // archived data is typically read-only
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o444)).unwrap();
+
+        let options = Options { preserve: Preserve::all(), ..Default::default() };
+        copy_file_with_hash_check(&file, &copy, &mut vec![0; 1024], &options).unwrap();
+        assert_eq!(fs::metadata(&copy).unwrap().permissions().mode() & 0o7777, 0o444);
+        if has_xattrs {
+            assert_eq!(xattr::get(&copy, "user.provenance").unwrap(), Some(b"run 42".to_vec()));
+        }
+    }
+
+    #[test]
+    fn test_default_keeps_the_mode_only() {
+        let tmp_dir = TempDir::new("metadata_default_test").unwrap();
+        let file = tmp_dir.path().join("a.bam");
+        let copy = tmp_dir.path().join("copy.bam");
+        fs::write(&file, "Test data").unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o600)).unwrap();
+        filetime::set_file_mtime(&file, FileTime::from_unix_time(2_000_000, 0)).unwrap();
+
+        copy_file_with_hash_check(&file, &copy, &mut vec![0; 1024], &Options::default()).unwrap();
+        assert_eq!(fs::metadata(&copy).unwrap().permissions().mode() & 0o7777, 0o600);
+        assert_ne!(times(&copy).1, FileTime::from_unix_time(2_000_000, 0));
+
+        assert_eq!("times,xattrs".parse::<Preserve>().unwrap(), Preserve { times: true, mode: false, xattrs: true, acls: false });
+        assert!("colours".parse::<Preserve>().is_err());
+    }
+}
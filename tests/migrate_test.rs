@@ -0,0 +1,58 @@
+//migrate_test.rs
+
+#[cfg(all(test, unix))]
+mod tests {
+
+    use tempdir::TempDir;
+    use file_copy_tool::*;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_migrate_archive() {
+        let tmp_dir = TempDir::new("migrate_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let old = tmp_dir.path().join("old");
+        let new = tmp_dir.path().join("new");
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::write(source.join("a.txt"), "Test data").unwrap();
+        fs::write(source.join("sub/b.txt"), "Test content").unwrap();
+        fs::write(source.join("c.txt"), "more").unwrap();
+        let options = Options::new(vec![Pattern::suffix(".txt")]);
+        for _ in 0..2 {
+            copy_files_matching_patterns(source.to_str().unwrap(), old.to_str().unwrap(), &options).unwrap();
+        }
+        // damaged in the old archive
+        fs::write(old.join("c.txt"), "bit rot").unwrap();
+
+        // a first run that got only one file across
+        let first = Options::new(vec![Pattern::parse("glob:a.txt").unwrap()]);
+        let report = migrate_archive(old.to_str().unwrap(), new.to_str().unwrap(), &[], &first).unwrap();
+        assert!(matches!(report.files[0].action, Action::Copied { .. }), "{:?}", report);
+        assert!(!report.has_failures());
+        assert_eq!(unmigrated_files(old.to_str().unwrap(), &report), vec![old.join("c.txt"), old.join("sub/b.txt")]);
+
+        let trees = vec![source.to_str().unwrap().to_string()];
+        let report = migrate_archive(old.to_str().unwrap(), new.to_str().unwrap(), &trees, &Options::default()).unwrap();
+        let action = |path: &Path| report.files.iter().filter(|f| f.path == path).map(|f| f.action.clone()).collect::<Vec<_>>();
+        assert!(matches!(action(&old.join("a.txt"))[..], [Action::Skipped { .. }]), "{:?}", report);
+        assert!(matches!(action(&old.join("sub/b.txt"))[..], [Action::Copied { .. }]), "{:?}", report);
+        assert!(matches!(action(&old.join("c.txt"))[..], [Action::Failed { .. }]), "{:?}", report);
+        assert!(matches!(action(&source.join("a.txt"))[..], [Action::Rebased { .. }]), "{:?}", report);
+        assert!(matches!(action(&source.join("sub/b.txt"))[..], [Action::Rebased { .. }]), "{:?}", report);
+        assert!(matches!(action(&source.join("c.txt"))[..], [Action::Failed { .. }]), "{:?}", report);
+        assert!(report.has_failures());
+
+        // the damaged file was not taken over and its link still points to the old archive
+        assert!(!new.join("c.txt").exists());
+        assert!(fs::read_link(source.join("c.txt")).unwrap().starts_with(fs::canonicalize(&old).unwrap()));
+        assert!(fs::read_link(source.join("a.txt")).unwrap().starts_with(fs::canonicalize(&new).unwrap()));
+        assert_eq!(fs::read_to_string(source.join("sub/b.txt")).unwrap(), "Test content");
+
+        let manifest = Manifest::load(&new).unwrap();
+        assert_eq!(manifest.len(), 2);
+        assert_eq!(fs::read_to_string(new.join(MANIFEST_NAME)).unwrap().lines().count(), 2);
+        let report = verify_archive(new.to_str().unwrap(), &Options::default()).unwrap();
+        assert_eq!(report.exit_code(), 0, "{:?}", report);
+    }
+}
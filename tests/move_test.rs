@@ -0,0 +1,96 @@
+//move_test.rs
+
+#[cfg(test)]
+mod tests {
+
+    use tempdir::TempDir;
+    use file_copy_tool::*;
+    use std::fs;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn test_move_mode() {
+        let tmp_dir = TempDir::new("move_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("b.txt"), "archived before").unwrap();
+        let options = Options::new(vec![Pattern::suffix(".txt")]);
+        copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        fs::write(source.join("a.txt"), "Test data").unwrap();
+        fs::write(source.join("c.txt"), "new run").unwrap();
+        fs::write(target.join("c.txt"), "old run").unwrap();
+
+        let mut options = options;
+        options.mode = Mode::Move;
+        let report = copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert!(matches!(report.files[0].action, Action::Moved { method: Some(_), .. }), "{:?}", report);
+        assert!(matches!(report.files[1].action, Action::Moved { method: None, .. }), "{:?}", report);
+        // a conflict is never deleted
+        assert!(matches!(report.files[2].action, Action::Skipped { .. }), "{:?}", report);
+        assert_eq!(report.bytes_freed, 9 + 15);
+
+        assert!(fs::symlink_metadata(source.join("a.txt")).is_err());
+        assert!(fs::symlink_metadata(source.join("b.txt")).is_err());
+        assert_eq!(fs::read_to_string(source.join("c.txt")).unwrap(), "new run");
+        assert_eq!(fs::read_to_string(target.join("a.txt")).unwrap(), "Test data");
+
+        // the removed files are on record and the fresh copy is in the manifest
+        let removed = fs::read_to_string(target.join(REMOVED_NAME)).unwrap();
+        assert_eq!(removed.lines().count(), 2, "{}", removed);
+        assert!(removed.contains(&source.join("a.txt").display().to_string()));
+        assert!(Manifest::load(&target).unwrap().get(Path::new("a.txt")).is_some());
+        let report = verify_archive(target.to_str().unwrap(), &Options::new(vec![Pattern::parse("glob:[ab].txt").unwrap()])).unwrap();
+        assert_eq!(report.files.len(), 2, "{:?}", report);
+        assert!(report.files.iter().all(|f| f.check == Check::Ok), "{:?}", report);
+    }
+
+    #[test]
+    fn test_move_keeps_a_changed_source() {
+        let tmp_dir = TempDir::new("move_changed_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        fs::create_dir_all(&source).unwrap();
+        let file = source.join("a.txt");
+        fs::write(&file, "Test data").unwrap();
+
+        // the source is written to while its copy is verified
+        let mut options = Options::new(vec![Pattern::suffix(".txt")]);
+        options.mode = Mode::Move;
+        let (changing, written) = (file.clone(), AtomicBool::new(false));
+        options.progress = Some(Progress::new(move |event| {
+            if let ProgressEvent::Bytes { path, phase: Phase::Hashing, .. } = event {
+                if path != changing && !written.swap(true, Ordering::SeqCst) {
+                    fs::write(&changing, "Test data, appended").unwrap();
+                }
+            }
+        }));
+        let report = copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert!(matches!(&report.files[0].action, Action::Failed { reason } if reason.contains("kept")), "{:?}", report);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "Test data, appended");
+        assert!(Manifest::load(&target).unwrap().get(Path::new("a.txt")).is_some());
+        assert!(!target.join(REMOVED_NAME).exists());
+    }
+
+    #[test]
+    fn test_removed_sources_are_absolute() {
+        // relative paths like on a command line started in the crate directory
+        let tmp_dir = TempDir::new_in("target", "move_relative_test").unwrap();
+        let relative = tmp_dir.path().strip_prefix(std::env::current_dir().unwrap()).unwrap_or(tmp_dir.path());
+        let source = relative.join("source");
+        let target = relative.join("target");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.txt"), "Test data").unwrap();
+        let abs_source = fs::canonicalize(&source).unwrap();
+
+        let mut options = Options::new(vec![Pattern::suffix(".txt")]);
+        options.mode = Mode::Move;
+        let report = copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert!(matches!(report.files[0].action, Action::Moved { .. }), "{:?}", report);
+
+        let removed = fs::read_to_string(target.join(REMOVED_NAME)).unwrap();
+        let hash = removed.split_whitespace().next().unwrap().to_string();
+        assert_eq!(removed, format!("{}  {}\n", hash, abs_source.join("a.txt").display()));
+    }
+}
@@ -0,0 +1,86 @@
+//pattern_test.rs
+
+#[cfg(test)]
+mod tests {
+
+    use tempdir::TempDir;
+    use file_copy_tool::*;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_pattern_kinds() {
+        let suffix = Pattern::parse(".fastq.gz").unwrap();
+        assert!(suffix.matches(Path::new("a/b/sample.fastq.gz")));
+        assert!(!suffix.matches(Path::new("a/b/sample.fastq")));
+
+        let glob = Pattern::parse("glob:**/raw/*.fastq.gz").unwrap();
+        assert!(glob.matches(Path::new("raw/sample.fastq.gz")));
+        assert!(glob.matches(Path::new("run1/raw/sample.fastq.gz")));
+        assert!(!glob.matches(Path::new("run1/raw/sub/sample.fastq.gz")));
+        assert!(!glob.matches(Path::new("run1/processed/sample.fastq.gz")));
+
+        // a glob without a '/' only looks at the file name
+        let name_glob = Pattern::parse("glob:*.bam").unwrap();
+        assert!(name_glob.matches(Path::new("deep/down/x.bam")));
+
+        let regex = Pattern::parse(r"re:^sample_\d+_R[12]\.fq\.gz$").unwrap();
+        assert!(regex.matches(Path::new("sample_12_R1.fq.gz")));
+        assert!(!regex.matches(Path::new("sample_12_R3.fq.gz")));
+        assert!(!regex.matches(Path::new("sub/sample_12_R1.fq.gz")));
+
+        assert!(matches!(Pattern::parse("re:(unclosed"), Err(Error::InvalidPattern { .. })));
+    }
+
+    #[test]
+    fn test_copy_and_revert_with_glob() {
+        let tmp_dir = TempDir::new("glob_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        fs::create_dir_all(source.join("run1/raw")).unwrap();
+        fs::create_dir_all(source.join("run1/processed")).unwrap();
+        fs::write(source.join("run1/raw/a.fastq.gz"), "raw data").unwrap();
+        fs::write(source.join("run1/processed/a.fastq.gz"), "processed data").unwrap();
+        let options = Options::new(vec![Pattern::parse("glob:**/raw/*.fastq.gz").unwrap()]);
+
+        copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert!(target.join("run1/raw/a.fastq.gz").exists());
+        assert!(!target.join("run1/processed/a.fastq.gz").exists());
+
+        copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert!(fs::symlink_metadata(source.join("run1/raw/a.fastq.gz")).unwrap().file_type().is_symlink());
+
+        let report = revert_links(source.to_str().unwrap(), &options).unwrap();
+        assert_eq!(report.files.len(), 1, "{:?}", report);
+        assert!(!fs::symlink_metadata(source.join("run1/raw/a.fastq.gz")).unwrap().file_type().is_symlink());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_excludes_and_pruned_dirs() {
+        let tmp_dir = TempDir::new("exclude_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        for dir in ["run1", "work/ab", ".snakemake", "tmp_1", "run1/tmp_2"] {
+            fs::create_dir_all(source.join(dir)).unwrap();
+            fs::write(source.join(dir).join("a.bam"), "bam").unwrap();
+        }
+        fs::write(source.join("run1/unsorted.bam"), "bam").unwrap();
+
+        let mut options = Options::new(vec![Pattern::suffix(".bam")]);
+        options.exclude_dirs = ["glob:work", "glob:.snakemake", "glob:tmp*"].iter().map(|dir| Pattern::parse(dir).unwrap()).collect();
+        options.exclude = vec![Pattern::parse("glob:unsorted*").unwrap()];
+
+        let report = copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert_eq!(report.files.len(), 1, "{:?}", report);
+        assert!(target.join("run1/a.bam").exists());
+
+        // link it and check that revert_links honours the same options
+        copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        fs::remove_dir_all(source.join("work")).unwrap();
+        std::os::unix::fs::symlink(target.join("run1/a.bam"), source.join("tmp_1/linked.bam")).unwrap();
+        let report = revert_links(source.to_str().unwrap(), &options).unwrap();
+        assert_eq!(report.files.len(), 1, "{:?}", report);
+        assert!(fs::symlink_metadata(source.join("tmp_1/linked.bam")).unwrap().file_type().is_symlink());
+    }
+}
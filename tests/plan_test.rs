@@ -0,0 +1,83 @@
+//plan_test.rs
+
+#[cfg(test)]
+mod tests {
+
+    use tempdir::TempDir;
+    use file_copy_tool::*;
+    use std::fs;
+    use std::process::Command;
+
+    #[test]
+    fn test_plan_does_not_touch_anything() {
+        let tmp_dir = TempDir::new("plan_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.txt"), "Test content").unwrap();
+        fs::write(source.join("b.txt"), "Test content").unwrap();
+        fs::write(source.join("c.not"), "Test content").unwrap();
+        let options = Options::new(vec![Pattern::suffix(".txt")]);
+
+        let plan = plan_copy(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert_eq!(plan.entries.len(), 2);
+        assert!(plan.entries.iter().all(|e| e.step == Step::Copy), "{:?}", plan);
+        assert!(!target.exists(), "planning must not create the target folder");
+
+        // the plan survives the trip through json
+        let plan: Plan = serde_json::from_str(&plan.to_json().unwrap()).unwrap();
+
+        // b.txt changes after planning and has to be refused
+        fs::write(source.join("b.txt"), "Changed content").unwrap();
+        let report = apply_plan(&plan, &options).unwrap();
+        assert_eq!(report.failed(), 1, "{:?}", report);
+        assert!(target.join("a.txt").exists());
+        assert!(!target.join("b.txt").exists());
+
+        // now a.txt would be linked
+        let plan = plan_copy(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        let a = plan.entries.iter().find(|e| e.source.ends_with("a.txt")).unwrap();
+        assert_eq!(a.step, Step::Link);
+        assert!(!fs::symlink_metadata(source.join("a.txt")).unwrap().file_type().is_symlink());
+
+        let report = apply_plan(&plan, &options).unwrap();
+        assert_eq!(report.exit_code(), 0, "{:?}", report);
+        assert!(fs::symlink_metadata(source.join("a.txt")).unwrap().file_type().is_symlink());
+        assert!(target.join("b.txt").exists());
+    }
+
+    #[test]
+    fn test_plan_and_apply_commands() {
+        let tmp_dir = TempDir::new("plan_cmd_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.txt"), "Test content").unwrap();
+
+        let is_release_mode = !cfg!(debug_assertions);
+        let exe = if is_release_mode { "./target/release/file_copy_tool" } else { "./target/debug/file_copy_tool" };
+
+        let output = Command::new(exe)
+            .args(["plan", source.to_str().unwrap(), target.to_str().unwrap(), ".txt"])
+            .output()
+            .expect("Failed to execute command");
+        assert!(output.status.success(), "plan failed: {}", String::from_utf8_lossy(&output.stderr));
+        let plan_file = tmp_dir.path().join("plan.json");
+        fs::write(&plan_file, &output.stdout).unwrap();
+        assert!(!target.exists());
+
+        let output = Command::new(exe)
+            .args(["apply", plan_file.to_str().unwrap()])
+            .output()
+            .expect("Failed to execute command");
+        assert!(output.status.success(), "apply failed: {}", String::from_utf8_lossy(&output.stderr));
+        assert!(target.join("a.txt").exists());
+
+        // a missing pattern is a usage error and must not be taken as a source folder named 'plan'
+        let output = Command::new(exe)
+            .args(["plan", source.to_str().unwrap(), target.to_str().unwrap()])
+            .output()
+            .expect("Failed to execute command");
+        assert_eq!(output.status.code(), Some(2));
+    }
+}
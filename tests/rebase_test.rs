@@ -0,0 +1,51 @@
+//rebase_test.rs
+
+#[cfg(all(test, unix))]
+mod tests {
+
+    use tempdir::TempDir;
+    use file_copy_tool::*;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_rebase_links() {
+        let tmp_dir = TempDir::new("rebase_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let old = tmp_dir.path().join("archive1");
+        let new = tmp_dir.path().join("archive2");
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::write(source.join("a.txt"), "Test data").unwrap();
+        fs::write(source.join("sub/b.txt"), "Test content").unwrap();
+        fs::write(source.join("c.fa"), "ACGT").unwrap();
+        fs::write(tmp_dir.path().join("reference.txt"), "not archived").unwrap();
+        std::os::unix::fs::symlink(tmp_dir.path().join("reference.txt"), source.join("reference.txt")).unwrap();
+
+        let options = Options::new(vec![Pattern::suffix(".txt")]);
+        let mut relative = Options::new(vec![Pattern::suffix(".fa")]);
+        relative.links = LinkStyle::Relative;
+        for _ in 0..2 {
+            copy_files_matching_patterns(source.to_str().unwrap(), old.to_str().unwrap(), &options).unwrap();
+            copy_files_matching_patterns(source.to_str().unwrap(), old.to_str().unwrap(), &relative).unwrap();
+        }
+
+        // the archive moved and one file got damaged on the way
+        fs::rename(&old, &new).unwrap();
+        fs::write(new.join("sub/b.txt"), "bit rot").unwrap();
+
+        let report = rebase_links(source.to_str().unwrap(), &old, &new, &Options::default()).unwrap();
+        assert_eq!(report.files.len(), 3, "{:?}", report);
+        let action = |name: &str| report.files.iter().find(|f| f.path == source.join(name)).map(|f| f.action.clone());
+        assert!(matches!(action("a.txt"), Some(Action::Rebased { .. })), "{:?}", report);
+        assert!(matches!(action("c.fa"), Some(Action::Rebased { .. })), "{:?}", report);
+        assert!(matches!(action("sub/b.txt"), Some(Action::Failed { .. })), "{:?}", report);
+        assert_eq!(report.exit_code(), 3);
+
+        assert_eq!(fs::read_link(source.join("a.txt")).unwrap(), fs::canonicalize(&new).unwrap().join("a.txt"));
+        assert_eq!(fs::read_link(source.join("c.fa")).unwrap(), Path::new("../archive2/c.fa"));
+        assert_eq!(fs::read_to_string(source.join("c.fa")).unwrap(), "ACGT");
+        // the broken link is left alone
+        assert!(fs::read_link(source.join("sub/b.txt")).unwrap().starts_with(fs::canonicalize(tmp_dir.path()).unwrap().join("archive1")));
+        assert_eq!(fs::read_link(source.join("reference.txt")).unwrap(), tmp_dir.path().join("reference.txt"));
+    }
+}
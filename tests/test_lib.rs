@@ -85,13 +85,13 @@ mod tests {
         let target_file_path = tmp_dir.join("target_file.txt");
 
         // Copy the file and check hash
-        assert!(copy_file_with_hash_check(&source_file_path, &target_file_path, &mut [0; 1024 * 1024]).is_ok());
+        assert!(copy_file_with_hash_check(&source_file_path, &target_file_path, &mut [0; 1024 * 1024], &Options::default()).is_ok());
 
         // Check if the target file is created
         assert!(fs::metadata(&target_file_path).is_ok());
 
         // Copying again should return an error as the target file already exists
-        assert!(copy_file_with_hash_check(&source_file_path, &target_file_path, &mut [0; 1024 * 1024]).is_err());
+        assert!(copy_file_with_hash_check(&source_file_path, &target_file_path, &mut [0; 1024 * 1024], &Options::default()).is_err());
 
         fs::remove_dir_all(&tmp_dir).unwrap();
     }
@@ -114,12 +114,12 @@ mod tests {
         let target_file_path = tmp_dir.join("target_file.txt");
 
         // Copy the file to that path
-        assert!(copy_file_with_hash_check(&source_file_path, &target_file_path, &mut [0; 1024 * 1024]).is_ok());
+        assert!(copy_file_with_hash_check(&source_file_path, &target_file_path, &mut [0; 1024 * 1024], &Options::default()).is_ok());
 
         // Replace the target file with a symlink
-        match replace_with_symlink(&source_file_path, &target_file_path) {
-            Some(_) => assert!(true,"as expedcted it worked?"),
-            None => panic!("replace_with_symlink has come back None"),
+        match replace_with_symlink(&source_file_path, &target_file_path, &Options::default()) {
+            Ok(_) => assert!(true,"as expedcted it worked?"),
+            Err(_) => panic!("replace_with_symlink has come back with an error"),
         };
         // Check if the symlink is created correctly
         let symlink = fs::read_link(&source_file_path);
@@ -131,4 +131,362 @@ mod tests {
         fs::remove_dir_all(&tmp_dir).unwrap();
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_copy_to_existing_target() {
+        let tmp_dir = TempDir::new("existing_target_test").unwrap();
+        let source_file_path = tmp_dir.path().join("source_file.txt");
+        let target_file_path = tmp_dir.path().join("target_file.txt");
+        fs::write(&source_file_path, "Test data").unwrap();
+        fs::write(&target_file_path, "other data").unwrap();
+
+        match copy_file_with_hash_check(&source_file_path, &target_file_path, &mut vec![0; 1024], &Options::default()) {
+            Err(Error::TargetExists { source, target }) => {
+                assert_eq!(source, source_file_path);
+                assert_eq!(target, target_file_path);
+            },
+            other => panic!("expected Error::TargetExists, got {:?}", other),
+        };
+        assert_eq!(fs::read_to_string(&target_file_path).unwrap(), "other data");
+    }
+
+    #[test]
+    fn test_relative_links() {
+        let tmp_dir = TempDir::new("relative_links_test").unwrap();
+        let project = tmp_dir.path().join("project");
+        fs::create_dir_all(project.join("source/sub")).unwrap();
+        fs::write(project.join("source/sub/a.txt"), "Test data").unwrap();
+        let mut options = Options::new(vec![Pattern::suffix(".txt")]);
+        options.links = LinkStyle::Relative;
+
+        for _ in 0..2 {
+            let source = project.join("source");
+            let target = project.join("backup");
+            copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        }
+        let link = fs::read_link(project.join("source/sub/a.txt")).unwrap();
+        assert_eq!(link, Path::new("../../backup/sub/a.txt"));
+
+        // the links still work after moving the whole project
+        let moved = tmp_dir.path().join("moved");
+        fs::rename(&project, &moved).unwrap();
+        assert_eq!(fs::read_to_string(moved.join("source/sub/a.txt")).unwrap(), "Test data");
+
+        let report = revert_links(moved.join("source").to_str().unwrap(), &options).unwrap();
+        assert_eq!(report.exit_code(), 0, "{:?}", report);
+        assert!(!fs::symlink_metadata(moved.join("source/sub/a.txt")).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(moved.join("source/sub/a.txt")).unwrap(), "Test data");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_hard_links() {
+        use std::os::unix::fs::MetadataExt;
+        let tmp_dir = TempDir::new("hard_links_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.txt"), "Test data").unwrap();
+        // not part of the run
+        fs::write(source.join("a.bak"), "keep me").unwrap();
+        let mut options = Options::new(vec![Pattern::suffix(".txt")]);
+        options.links = LinkStyle::HardOnly;
+
+        copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        let report = copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert!(matches!(report.files[0].action, Action::Linked { .. }), "{:?}", report);
+        let file = source.join("a.txt");
+        assert!(!fs::symlink_metadata(&file).unwrap().file_type().is_symlink());
+        assert_eq!(fs::metadata(&file).unwrap().ino(), fs::metadata(target.join("a.txt")).unwrap().ino());
+        assert_eq!(fs::read_to_string(source.join("a.bak")).unwrap(), "keep me");
+        assert_eq!(fs::read_dir(&source).unwrap().count(), 2);
+
+        // nothing left to do for the next run
+        let report = copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert!(matches!(&report.files[0].action, Action::Skipped { reason } if reason.contains("hard link")), "{:?}", report);
+
+        // a plain revert leaves hard links alone
+        let report = revert_links(source.to_str().unwrap(), &Options::new(vec![Pattern::suffix(".txt")])).unwrap();
+        assert!(report.files.is_empty(), "{:?}", report);
+
+        // the link style of the copy does not select them either
+        let report = revert_links(source.to_str().unwrap(), &options).unwrap();
+        assert!(report.files.is_empty(), "{:?}", report);
+
+        // neither does a hard link into another backup
+        options.break_hard_links = true;
+        options.backup_root = Some(tmp_dir.path().join("elsewhere"));
+        let report = revert_links(source.to_str().unwrap(), &options).unwrap();
+        assert!(report.files.is_empty(), "{:?}", report);
+
+        options.backup_root = Some(target.clone());
+        let report = revert_links(source.to_str().unwrap(), &options).unwrap();
+        assert!(matches!(report.files[0].action, Action::Restored { .. }), "{:?}", report);
+        assert_ne!(fs::metadata(&file).unwrap().ino(), fs::metadata(target.join("a.txt")).unwrap().ino());
+        assert_eq!(fs::metadata(&file).unwrap().nlink(), 1);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "Test data");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_revert_symlink_keeps_the_link_on_failure() {
+        let tmp_dir = TempDir::new("revert_safe_test").unwrap();
+        let data = tmp_dir.path().join("data.txt");
+        let link = tmp_dir.path().join("link.txt");
+        std::os::unix::fs::symlink(&data, &link).unwrap();
+
+        // the link target is gone
+        assert!(revert_symlink(&link, &mut vec![0; 1024], &Options::default()).is_err());
+        assert_eq!(fs::read_link(&link).unwrap(), data);
+        assert_eq!(fs::read_dir(tmp_dir.path()).unwrap().count(), 1, "a temporary file was left behind");
+
+        fs::write(&data, "Test data").unwrap();
+        assert_eq!(revert_symlink(&link, &mut vec![0; 1024], &Options::default()).unwrap(), Some(data.clone()));
+        assert!(!fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&link).unwrap(), "Test data");
+        assert_eq!(fs::read_dir(tmp_dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_revert_only_links_into_the_backup_root() {
+        let tmp_dir = TempDir::new("backup_root_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let backup = tmp_dir.path().join("backup");
+        let reference = tmp_dir.path().join("shared/genome.fa");
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(reference.parent().unwrap()).unwrap();
+        fs::write(&reference, "ACGT").unwrap();
+        fs::write(source.join("a.fa"), "Test data").unwrap();
+        let mut options = Options::new(vec![Pattern::suffix(".fa")]);
+        options.links = LinkStyle::Relative;
+        for _ in 0..2 {
+            copy_files_matching_patterns(source.to_str().unwrap(), backup.to_str().unwrap(), &options).unwrap();
+        }
+        // created by hand
+        std::os::unix::fs::symlink(&reference, source.join("genome.fa")).unwrap();
+
+        options.backup_root = Some(backup.clone());
+        let report = revert_links(source.to_str().unwrap(), &options).unwrap();
+        assert_eq!(report.files.len(), 1, "{:?}", report);
+        assert!(!fs::symlink_metadata(source.join("a.fa")).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_link(source.join("genome.fa")).unwrap(), reference);
+    }
+
+    #[test]
+    fn test_missing_files_are_errors() {
+        let tmp_dir = TempDir::new("missing_test").unwrap();
+        let missing = tmp_dir.path().join("missing.txt");
+
+        match calculate_sha256(&missing, &mut vec![0; 1024]) {
+            Err(Error::Io { path, .. }) => assert_eq!(path, missing),
+            other => panic!("expected Error::Io, got {:?}", other),
+        };
+
+        match replace_with_symlink(&missing, &tmp_dir.path().join("not_there_either.txt"), &Options::default()) {
+            Err(Error::Io { .. }) => (),
+            other => panic!("expected Error::Io, got {:?}", other),
+        };
+
+        assert!(copy_files_matching_patterns(missing.to_str().unwrap(), "whatever", &Options::new(vec![Pattern::suffix(".txt")])).is_err());
+    }
+
+    #[test]
+    fn test_run_report() {
+        let tmp_dir = TempDir::new("report_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::write(source.join("a.txt"), "12345").unwrap();
+        fs::write(source.join("sub/b.txt"), "123").unwrap();
+        fs::write(source.join("c.not"), "not copied").unwrap();
+        let options = Options::new(vec![Pattern::suffix(".txt")]);
+
+        let report = copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert_eq!(report.files.len(), 2, "{:?}", report);
+        assert!(report.files.iter().all(|f| matches!(f.action, Action::Copied { .. })), "{:?}", report);
+        assert_eq!(report.bytes_copied, 8);
+        assert_eq!(report.bytes_freed, 0);
+        assert_eq!(report.exit_code(), 0);
+
+        let report = copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert!(report.files.iter().all(|f| matches!(f.action, Action::Linked { .. })), "{:?}", report);
+        assert_eq!(report.bytes_copied, 0);
+        assert_eq!(report.bytes_freed, 8);
+
+        // the third run has nothing left to do
+        let report = copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert!(report.files.iter().all(|f| matches!(f.action, Action::Skipped { .. })), "{:?}", report);
+
+        let report = revert_links(source.to_str().unwrap(), &options).unwrap();
+        assert!(report.files.iter().all(|f| matches!(f.action, Action::Restored { .. })), "{:?}", report);
+        assert_eq!(report.bytes_copied, 8);
+        assert!(!fs::symlink_metadata(source.join("a.txt")).unwrap().file_type().is_symlink());
+    }
+
+    #[test]
+    fn test_parallel_report_is_deterministic() {
+        let tmp_dir = TempDir::new("parallel_test").unwrap();
+        let mut reports = Vec::new();
+        for workers in [1, 8] {
+            let source = tmp_dir.path().join(format!("source_{workers}"));
+            let target = tmp_dir.path().join(format!("target_{workers}"));
+            for i in 0..40 {
+                let dir = source.join(format!("dir_{}", i % 5));
+                fs::create_dir_all(&dir).unwrap();
+                fs::write(dir.join(format!("file_{i}.txt")), "x".repeat(i * 1000)).unwrap();
+            }
+            let mut options = Options::new(vec![Pattern::suffix(".txt")]);
+            options.workers = workers;
+
+            let report = copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+            assert_eq!(report.exit_code(), 0, "{:?}", report);
+            let files: Vec<_> = report.files.iter()
+                .map(|f| f.path.strip_prefix(&source).unwrap().to_path_buf())
+                .collect();
+            reports.push((files, report.bytes_copied));
+        }
+        assert_eq!(reports[0], reports[1]);
+    }
+
+    #[test]
+    fn test_parallel_links_side_by_side() {
+        let tmp_dir = TempDir::new("parallel_links_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        fs::create_dir_all(&source).unwrap();
+        for i in 0..20 {
+            fs::write(source.join(format!("sample_{i}.bam")), format!("reads {i}")).unwrap();
+            fs::write(source.join(format!("sample_{i}.bai")), format!("index {i}")).unwrap();
+        }
+        // not part of the run, but named like a scratch file of the files next to it
+        fs::write(source.join("sample_0.bak"), "keep me").unwrap();
+        let mut options = Options::new(vec![Pattern::parse("re:\\.ba[mi]$").unwrap()]);
+        options.workers = 8;
+
+        for _ in 0..2 {
+            let report = copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+            assert_eq!(report.exit_code(), 0, "{:?}", report);
+        }
+        for i in 0..20 {
+            assert!(fs::symlink_metadata(source.join(format!("sample_{i}.bam"))).unwrap().file_type().is_symlink());
+            assert_eq!(fs::read_to_string(source.join(format!("sample_{i}.bam"))).unwrap(), format!("reads {i}"));
+            assert_eq!(fs::read_to_string(source.join(format!("sample_{i}.bai"))).unwrap(), format!("index {i}"));
+        }
+        assert_eq!(fs::read_to_string(source.join("sample_0.bak")).unwrap(), "keep me");
+        assert_eq!(fs::read_dir(&source).unwrap().count(), 41);
+    }
+
+    #[test]
+    fn test_copy_with_sha256() {
+        let tmp_dir = TempDir::new("single_pass_test").unwrap();
+        let source = tmp_dir.path().join("source.bin");
+        let target = tmp_dir.path().join("target.bin");
+        // more than one buffer full
+        let content: Vec<u8> = (0..3000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(&source, &content).unwrap();
+
+        let mut buffer = vec![0; 1024];
+        let hash = copy_with_sha256(&source, &target, &mut buffer).unwrap();
+        assert_eq!(hash, calculate_sha256(&source, &mut buffer).unwrap());
+        assert_eq!(fs::read(&target).unwrap(), content);
+
+        // the target is never overwritten
+        assert!(copy_with_sha256(&source, &target, &mut buffer).is_err());
+        assert_eq!(fs::read(&target).unwrap(), content);
+
+        let copy = tmp_dir.path().join("sub/copy.bin");
+        assert_eq!(copy_file_with_hash_check(&source, &copy, &mut buffer, &Options::default()).unwrap(), hash);
+    }
+
+    #[test]
+    fn test_copy_method_is_reported() {
+        let tmp_dir = TempDir::new("copy_method_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        fs::create_dir_all(&source).unwrap();
+        let content: Vec<u8> = (0..3_000_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(source.join("a.bin"), &content).unwrap();
+
+        // whichever way the data got there - it is verified like a buffered copy
+        let report = copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &Options::new(vec![Pattern::suffix(".bin")])).unwrap();
+        let (hash, method) = match &report.files[0].action {
+            Action::Copied { hash, method, .. } => (hash.clone(), *method),
+            other => panic!("expected a copy, got {:?}", other),
+        };
+        assert_eq!(fs::read(target.join("a.bin")).unwrap(), content);
+        assert_eq!(hash, calculate_sha256(&source.join("a.bin"), &mut vec![0; 1024]).unwrap());
+        assert!(report.files[0].to_string().ends_with(&format!("({})", method)));
+    }
+
+    #[test]
+    fn test_progress_is_reported() {
+        let tmp_dir = TempDir::new("progress_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.bin"), vec![7u8; 3_000_000]).unwrap();
+        fs::write(source.join("b.bin"), "small").unwrap();
+
+        let copied = std::sync::Arc::new(std::sync::Mutex::new((0u64, 0u64, 0u64)));
+        let mut options = Options::new(vec![Pattern::suffix(".bin")]);
+        options.workers = 2;
+        let counts = std::sync::Arc::clone(&copied);
+        options.progress = Some(Progress::new(move |event| {
+            let mut counts = counts.lock().unwrap();
+            match event {
+                ProgressEvent::Bytes { phase: Phase::Copying, bytes, .. } => counts.0 += bytes,
+                ProgressEvent::Bytes { phase: Phase::Hashing, bytes, .. } => counts.1 += bytes,
+                ProgressEvent::FileDone { bytes, .. } => counts.2 += bytes,
+            }
+        }));
+        copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        // every copy is read back once to verify it, a fast copy hashes its source as well
+        let (copying, hashing, done) = *copied.lock().unwrap();
+        assert_eq!(copying, 3_000_005);
+        assert!(hashing == 3_000_005 || hashing == 2 * 3_000_005, "{}", hashing);
+        assert_eq!(done, 3_000_005);
+
+        // linking hashes both sides
+        *copied.lock().unwrap() = (0, 0, 0);
+        copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert_eq!(*copied.lock().unwrap(), (0, 2 * 3_000_005, 3_000_005));
+    }
+
+    #[test]
+    fn test_report_as_json() {
+        let file = FileReport {
+            path: "work/a.bam".into(),
+            action: Action::Copied { target: "backup/a.bam".into(), bytes: 3, hash: "abc".to_string(), method: CopyMethod::Reflink },
+        };
+        assert_eq!(serde_json::to_string(&file).unwrap(),
+            r#"{"path":"work/a.bam","action":"copied","target":"backup/a.bam","bytes":3,"hash":"abc","method":"reflink"}"#);
+        let file = FileReport { path: "work/b.bam".into(), action: Action::Failed { reason: "gone".to_string() } };
+        assert_eq!(serde_json::to_string(&file).unwrap(), r#"{"path":"work/b.bam","action":"failed","reason":"gone"}"#);
+        let check = FileCheck { path: "a.bam".into(), check: Check::Modified { expected: "a".to_string(), found: "b".to_string() } };
+        assert_eq!(serde_json::to_string(&check).unwrap(), r#"{"path":"a.bam","check":"modified","expected":"a","found":"b"}"#);
+    }
+
+    #[test]
+    fn test_copy_is_atomic() {
+        let tmp_dir = TempDir::new("atomic_test").unwrap();
+        let source = tmp_dir.path().join("a.txt");
+        let target_dir = tmp_dir.path().join("target");
+        let target = target_dir.join("a.txt");
+        let partial = target_dir.join(".a.txt.partial");
+        fs::write(&source, "Test data").unwrap();
+
+        // a killed run left a truncated temporary file behind
+        fs::create_dir_all(&target_dir).unwrap();
+        fs::write(&partial, "Test").unwrap();
+        copy_file_with_hash_check(&source, &target, &mut vec![0; 1024], &Options::default()).unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "Test data");
+        assert!(!partial.exists());
+
+        // a failing copy leaves nothing under either name
+        let failing = target_dir.join("dir_copy");
+        assert!(copy_file_with_hash_check(tmp_dir.path(), &failing, &mut vec![0; 1024], &Options::default()).is_err());
+        assert!(!failing.exists());
+        assert!(!target_dir.join(".dir_copy.partial").exists());
+    }
+
+}
@@ -0,0 +1,52 @@
+//throttle_test.rs
+
+#[cfg(test)]
+mod tests {
+
+    use tempdir::TempDir;
+    use file_copy_tool::*;
+    use std::fs;
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    #[test]
+    fn test_parse_limits() {
+        assert_eq!(parse_rate("200M").unwrap(), 200 << 20);
+        assert_eq!(parse_rate("1g").unwrap(), 1 << 30);
+        assert_eq!(parse_rate("512KiB").unwrap(), 512 << 10);
+        assert_eq!(parse_rate("1000").unwrap(), 1000);
+        for rate in ["", "M", "0M", "-1M", "1.5G", "200X", "99999999999T"] {
+            assert!(matches!(parse_rate(rate), Err(Error::InvalidOption { .. })), "{}", rate);
+        }
+
+        let day: Hours = "08:00-18:30".parse().unwrap();
+        assert!(day.contains(8 * 60) && day.contains(18 * 60 + 29));
+        assert!(!day.contains(18 * 60 + 30) && !day.contains(7 * 60));
+        let night: Hours = "22:00-06:00".parse().unwrap();
+        assert!(night.contains(23 * 60) && night.contains(60));
+        assert!(!night.contains(12 * 60));
+        for hours in ["08:00", "8-18", "08:00-08:00", "25:00-06:00", "08:60-09:00"] {
+            assert!(hours.parse::<Hours>().is_err(), "{}", hours);
+        }
+    }
+
+    #[test]
+    fn test_rate_is_shared_by_all_workers() {
+        let tmp_dir = TempDir::new("throttle_test").unwrap();
+        let source = tmp_dir.path().join("source");
+        let target = tmp_dir.path().join("target");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.bin"), vec![1u8; 1 << 20]).unwrap();
+        fs::write(source.join("b.bin"), vec![2u8; 1 << 20]).unwrap();
+
+        let mut options = Options::new(vec![Pattern::suffix(".bin")]);
+        options.workers = 2;
+        options.throttle = Some(Arc::new(Throttle::new(Some(2 << 20), None, None)));
+        let started = Instant::now();
+        let report = copy_files_matching_patterns(source.to_str().unwrap(), target.to_str().unwrap(), &options).unwrap();
+        assert_eq!(report.exit_code(), 0, "{:?}", report);
+        // each file is copied and read back: at least 4 MiB at 2 MiB per second
+        assert!(started.elapsed().as_secs_f64() > 1.5, "{:?}", started.elapsed());
+        assert_eq!(fs::read(target.join("b.bin")).unwrap(), vec![2u8; 1 << 20]);
+    }
+}